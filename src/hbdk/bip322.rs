@@ -0,0 +1,294 @@
+//! BIP-322 proofs of control of an address of the wallet
+//!
+//! The proofs use the full format: a virtual "to_spend" trx commits to the message and pays to the
+//! address, and the cosigners sign the "to_sign" trx spending it, as a regular psbt their hardware
+//! wallets can sign. The finalized "to_sign" trx is the signature of the message.
+
+use crate::hbdk::errors::Error;
+use crate::hbdk::{Cosigner, Descriptors, Wallet};
+use bdk::database::Database;
+use bdk::LocalUtxo;
+use bitcoin::blockdata::opcodes::all::OP_RETURN;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::util::address::Address;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::util::sighash::Prevouts;
+use bitcoin::{
+    consensus, OutPoint, PackedLockTime, Script, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use rocket::serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+const MESSAGE_TAG: &[u8] = b"BIP0322-signed-message";
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MessageProofRequest {
+    pub descriptors: Descriptors,
+    pub address: String,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignedMessageProof {
+    pub descriptors: Descriptors,
+    pub address: String,
+    pub message: String,
+    pub psbts: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MessageProofVerification {
+    pub address: String,
+    pub message: String,
+    /// Cosigners that signed the message, only set for multisig wallets
+    pub signers: Vec<Cosigner>,
+    /// BIP-322 full signature of the message, the base64 encoded "to_sign" trx
+    pub signature: String,
+}
+
+/// Returns the BIP-322 "to_sign" psbt proving control of the address of the wallet
+pub fn create_proof(wallet: &Wallet, address: &str, message: &str) -> Result<String, Error> {
+    let script_pubkey = wallet_script_pubkey(wallet, address)?;
+    let keychain = match wallet
+        .wallet
+        .database()
+        .get_path_from_script_pubkey(&script_pubkey)?
+    {
+        Some((keychain, _)) => keychain,
+        None => {
+            return Err(Error::new(&format!(
+                "address does not belong to the wallet: {}",
+                address
+            )))
+        }
+    };
+    let to_spend = to_spend(&script_pubkey, message);
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(to_sign(&to_spend))?;
+    let mut input = wallet.wallet.get_psbt_input(
+        LocalUtxo {
+            outpoint: OutPoint::new(to_spend.txid(), 0),
+            txout: to_spend.output[0].clone(),
+            keychain,
+            is_spent: false,
+        },
+        None,
+        false,
+    )?;
+    input.witness_utxo = Some(to_spend.output[0].clone());
+    input.non_witness_utxo = Some(to_spend);
+    psbt.inputs[0] = input;
+    Ok(wallet.serialize_psbt(&psbt))
+}
+
+/// Finalizes the signed "to_sign" psbts and verifies the signature of the message
+pub fn verify_proof(
+    wallet: &Wallet,
+    address: &str,
+    message: &str,
+    psbts: &[String],
+) -> Result<MessageProofVerification, Error> {
+    let script_pubkey = wallet_script_pubkey(wallet, address)?;
+    let to_sign = wallet.finalize_proof(psbts, 0)?.extract_tx();
+    verify_signature(&script_pubkey, message, &to_sign, wallet.wallet.secp_ctx())?;
+    let signers = match wallet.get_multisig() {
+        Ok(multisig) => wallet
+            .get_output_signers(
+                &to_spend(&script_pubkey, message).output[0],
+                &to_sign.input[0].script_sig,
+                &to_sign.input[0].witness,
+                &multisig,
+            )?
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    Ok(MessageProofVerification {
+        address: address.to_string(),
+        message: message.to_string(),
+        signers,
+        signature: base64::encode(consensus::serialize(&to_sign)),
+    })
+}
+
+/// Returns the script pubkey of the address, checking that it belongs to the network of the
+/// wallet, the wallet is synced so that its addresses are known
+fn wallet_script_pubkey(wallet: &Wallet, address: &str) -> Result<Script, Error> {
+    let parsed = Address::from_str(address)?;
+    if !parsed.is_valid_for_network(wallet.blockchain.network) {
+        return Err(Error::new(&format!(
+            "address: {} does not belong to the network: {}",
+            address, wallet.blockchain.network
+        )));
+    }
+    wallet.sync()?;
+    Ok(parsed.script_pubkey())
+}
+
+/// Checks that the "to_sign" trx commits to the message and satisfies the script pubkey
+fn verify_signature<C: Verification>(
+    script_pubkey: &Script,
+    message: &str,
+    to_sign: &Transaction,
+    secp: &Secp256k1<C>,
+) -> Result<(), Error> {
+    let to_spend = to_spend(script_pubkey, message);
+    let expected = self::to_sign(&to_spend);
+    if to_sign.version != expected.version
+        || to_sign.lock_time != expected.lock_time
+        || to_sign.input.len() != 1
+        || to_sign.input[0].previous_output != expected.input[0].previous_output
+        || to_sign.input[0].sequence != expected.input[0].sequence
+        || to_sign.output != expected.output
+    {
+        return Err(Error::new(
+            "the proof is not a to_sign trx of the address and message",
+        ));
+    }
+    let interpreter = Wallet::get_interpreter(
+        script_pubkey,
+        &to_sign.input[0].script_sig,
+        &to_sign.input[0].witness,
+    )?;
+    let prevouts = Prevouts::All(&to_spend.output);
+    for constraint in interpreter.iter(secp, to_sign, 0, &prevouts) {
+        constraint.map_err(|err| Error::new(&format!("invalid message signature, {}", err)))?;
+    }
+    Ok(())
+}
+
+fn message_hash(message: &str) -> sha256::Hash {
+    let tag = sha256::Hash::hash(MESSAGE_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag);
+    engine.input(&tag);
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine)
+}
+
+/// Returns the virtual trx committing to the message and paying to the script pubkey
+fn to_spend(script_pubkey: &Script, message: &str) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: PackedLockTime(0),
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), 0xFFFFFFFF),
+            script_sig: Builder::new()
+                .push_int(0)
+                .push_slice(&message_hash(message))
+                .into_script(),
+            sequence: Sequence(0),
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    }
+}
+
+/// Returns the unsigned virtual trx spending the "to_spend" trx
+fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: PackedLockTime(0),
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.txid(), 0),
+            script_sig: Script::new(),
+            sequence: Sequence(0),
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn get_test_script_pubkey() -> Script {
+        Address::from_str("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l")
+            .unwrap()
+            .script_pubkey()
+    }
+
+    /// Returns the "to_sign" trx of the test vectors of BIP-322 signed with the simple signature
+    fn get_test_to_sign(message: &str, signature: &str) -> Transaction {
+        let mut to_sign = to_sign(&to_spend(&get_test_script_pubkey(), message));
+        to_sign.input[0].witness =
+            consensus::deserialize(&base64::decode(signature).unwrap()).unwrap();
+        to_sign
+    }
+
+    #[test]
+    fn test_message_hash() {
+        assert_eq!(
+            message_hash("").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            message_hash("Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+    }
+
+    #[test]
+    fn test_to_spend_and_to_sign() {
+        let script_pubkey = get_test_script_pubkey();
+        let to_spend = to_spend(&script_pubkey, "");
+        assert_eq!(
+            to_spend.txid().to_string(),
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7"
+        );
+        assert_eq!(
+            to_sign(&to_spend).txid().to_string(),
+            "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"
+        );
+        let to_spend = self::to_spend(&script_pubkey, "Hello World");
+        assert_eq!(
+            to_spend.txid().to_string(),
+            "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b"
+        );
+        assert_eq!(
+            to_sign(&to_spend).txid().to_string(),
+            "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf"
+        );
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let secp = Secp256k1::verification_only();
+        let to_sign = get_test_to_sign(
+            "Hello World",
+            "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+        );
+        verify_signature(&get_test_script_pubkey(), "Hello World", &to_sign, &secp).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "the proof is not a to_sign trx of the address and message")]
+    fn test_verify_signature_should_fail_for_another_message() {
+        let secp = Secp256k1::verification_only();
+        let to_sign = get_test_to_sign(
+            "Hello World",
+            "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+        );
+        verify_signature(&get_test_script_pubkey(), "", &to_sign, &secp).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid message signature")]
+    fn test_verify_signature_should_fail_for_invalid_signature() {
+        let secp = Secp256k1::verification_only();
+        // Signature of the empty message
+        let to_sign = get_test_to_sign(
+            "Hello World",
+            "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+        );
+        verify_signature(&get_test_script_pubkey(), "Hello World", &to_sign, &secp).unwrap();
+    }
+}
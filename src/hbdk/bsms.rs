@@ -0,0 +1,430 @@
+//! Multisig setup coordinator exchanging plain text key and descriptor records
+//!
+//! The coordinator creates a session with a token for each signer, collects the signed key
+//! records of the signers and, once all of them have been received, generates the descriptor
+//! record for the signers to verify and register the multisig. The records use the layout of the
+//! BSMS 1.0 records without their encryption, so this is not a complete BIP-129 coordinator:
+//! records are exchanged in plain text, the tokens only authenticate the signers, and signers that
+//! require encrypted records can not take part. Each token is only returned once, when the
+//! session is created, and must be handed to its signer privately.
+
+use crate::hbdk::errors::Error;
+use crate::hbdk::storage::Storage;
+use crate::hbdk::{util, Cosigner, Multisig};
+use bdk::descriptor::{Descriptor, DescriptorPublicKey};
+use bitcoin::util::bip32::ExtendedPubKey;
+use bitcoin::Network;
+use bitcoin_hashes::hex::ToHex;
+use core::str::FromStr;
+use rand::Rng;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const BSMS_VERSION: &str = "BSMS 1.0";
+/// Maximum number of keys allowed by a sortedmulti descriptor
+pub const MAX_SIGNERS: u32 = 20;
+const SESSIONS_COLLECTION: &str = "bsms_sessions";
+
+/// Key record sent by a signer in the first round of the setup
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct KeyRecord {
+    pub token: String,
+    /// Key with its origin, i.e. [xfp/derivation_path]xpub
+    pub key: String,
+    pub description: String,
+    /// Base64 encoded signature of the first four lines of the record, generated with the
+    /// private key of the xpub
+    pub signature: String,
+}
+
+impl KeyRecord {
+    fn signed_message(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            BSMS_VERSION, self.token, self.key, self.description
+        )
+    }
+
+    /// Verifies the key origin and the signature of the key record and returns the cosigner for
+    /// the key
+    pub fn verify(&self) -> Result<Cosigner, Error> {
+        let mut cosigner = Cosigner::from_str(&self.key)?;
+        if cosigner.xfp.is_none() {
+            return Err(Error::new(&format!(
+                "key record key must include its origin, key: {}",
+                self.key
+            )));
+        }
+        cosigner.verify_key_origin()?;
+        let xpub = ExtendedPubKey::from_str(&util::to_legacy_xpub(&cosigner.xpub)?)?;
+        if !util::verify_message_signature(
+            &xpub.public_key,
+            &self.signed_message(),
+            &self.signature,
+        )? {
+            return Err(Error::new(&format!(
+                "key record signature was not generated by the key: {}",
+                self.key
+            )));
+        }
+        cosigner.xpub = util::to_segwit_native_multisig_xpub(&cosigner.xpub)?;
+        Ok(cosigner)
+    }
+}
+
+impl FromStr for KeyRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let lines: Vec<&str> = s.trim().lines().map(|line| line.trim()).collect();
+        if let [version, token, key, description, signature] = lines.as_slice() {
+            if *version != BSMS_VERSION {
+                return Err(Error::new(&format!(
+                    "unsupported key record version: {}",
+                    version
+                )));
+            }
+            return Ok(KeyRecord {
+                token: token.to_lowercase(),
+                key: key.to_string(),
+                description: description.to_string(),
+                signature: signature.to_string(),
+            });
+        }
+        Err(Error::new(&format!(
+            "key record must have 5 lines, found: {}",
+            lines.len()
+        )))
+    }
+}
+
+/// Stored setup session, it is never returned to the clients as it holds the tokens of all the
+/// signers
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BsmsSession {
+    pub id: String,
+    pub threshold: u32,
+    /// Token of each signer, in the order in which the signers were added to the session
+    pub tokens: Vec<String>,
+    /// Received key records by token
+    pub key_records: BTreeMap<String, KeyRecord>,
+}
+
+impl BsmsSession {
+    pub fn new(threshold: u32, signers: u32) -> Result<Self, Error> {
+        if threshold == 0 || threshold > signers || signers > MAX_SIGNERS {
+            return Err(Error::new(&format!(
+                "invalid multisig policy: {} of {}, the threshold must be between 1 and the number of signers, and there can be at most {} signers",
+                threshold, signers, MAX_SIGNERS
+            )));
+        }
+        Ok(BsmsSession {
+            id: random_hex(16),
+            threshold,
+            tokens: (0..signers).map(|_| random_hex(8)).collect(),
+            key_records: BTreeMap::new(),
+        })
+    }
+
+    pub fn add_key_record(&mut self, key_record: KeyRecord) -> Result<(), Error> {
+        if !self.tokens.contains(&key_record.token) {
+            return Err(Error::new(&format!(
+                "unknown token: {} for session: {}",
+                key_record.token, self.id
+            )));
+        }
+        if self.key_records.contains_key(&key_record.token) {
+            return Err(Error::new(&format!(
+                "key record already received for token: {}",
+                key_record.token
+            )));
+        }
+        let cosigner = key_record.verify()?;
+        for received in self.key_records.values() {
+            if received.verify()?.xpub == cosigner.xpub {
+                return Err(Error::new(&format!(
+                    "key already added to the session, key: {}",
+                    key_record.key
+                )));
+            }
+        }
+        self.key_records
+            .insert(key_record.token.clone(), key_record);
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.key_records.len() == self.tokens.len()
+    }
+
+    /// Returns the multisig for the session once all key records have been received
+    pub fn multisig(&self) -> Result<Multisig, Error> {
+        if !self.is_complete() {
+            return Err(Error::new(&format!(
+                "missing key records, received: {} of {}",
+                self.key_records.len(),
+                self.tokens.len()
+            )));
+        }
+        let mut multisig = Multisig::new(self.threshold);
+        for token in &self.tokens {
+            multisig.add_cosigner(self.key_records[token].verify()?);
+        }
+        Ok(multisig)
+    }
+}
+
+/// Session returned to its creator, with the token to hand to each signer
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CreatedBsmsSession {
+    pub id: String,
+    pub threshold: u32,
+    pub tokens: Vec<String>,
+}
+
+impl From<&BsmsSession> for CreatedBsmsSession {
+    fn from(session: &BsmsSession) -> Self {
+        CreatedBsmsSession {
+            id: session.id.clone(),
+            threshold: session.threshold,
+            tokens: session.tokens.clone(),
+        }
+    }
+}
+
+/// Progress of a session, without the tokens and key records of the signers
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct BsmsSessionStatus {
+    pub id: String,
+    pub threshold: u32,
+    pub signers: u32,
+    pub received_key_records: u32,
+    pub complete: bool,
+}
+
+impl From<&BsmsSession> for BsmsSessionStatus {
+    fn from(session: &BsmsSession) -> Self {
+        BsmsSessionStatus {
+            id: session.id.clone(),
+            threshold: session.threshold,
+            signers: session.tokens.len() as u32,
+            received_key_records: session.key_records.len() as u32,
+            complete: session.is_complete(),
+        }
+    }
+}
+
+/// Generates the descriptor record sent to the signers in the second round of the setup
+pub fn descriptor_record(multisig: &Multisig, network: Network) -> Result<String, Error> {
+    let descriptor = multisig.descriptor(false)?;
+    let first_address = Descriptor::<DescriptorPublicKey>::from_str(&descriptor)?
+        .at_derivation_index(0)
+        .address(network)?;
+    Ok(format!(
+        "{}\n{}\n/0/*,/1/*\n{}",
+        BSMS_VERSION,
+        descriptor.replace("/0/*", "/**"),
+        first_address
+    ))
+}
+
+/// Coordinates the setup sessions, which are kept in the local storage between the rounds
+pub struct Coordinator<'a> {
+    storage: &'a Storage,
+}
+
+impl<'a> Coordinator<'a> {
+    pub fn new(storage: &'a Storage) -> Self {
+        Coordinator { storage }
+    }
+
+    pub fn create_session(
+        &self,
+        threshold: u32,
+        signers: u32,
+    ) -> Result<CreatedBsmsSession, Error> {
+        let session = BsmsSession::new(threshold, signers)?;
+        self.storage
+            .save(SESSIONS_COLLECTION, &session.id, &session)?;
+        Ok(CreatedBsmsSession::from(&session))
+    }
+
+    pub fn get_session(&self, session_id: &str) -> Result<BsmsSession, Error> {
+        self.storage
+            .load(SESSIONS_COLLECTION, session_id)?
+            .ok_or_else(|| Error::new(&format!("bsms session not found: {}", session_id)))
+    }
+
+    /// Adds the key record to the session, the session is locked while it is updated so that
+    /// the key records of concurrent signers are not lost
+    pub fn add_key_record(
+        &self,
+        session_id: &str,
+        key_record: &str,
+    ) -> Result<BsmsSessionStatus, Error> {
+        let key_record = KeyRecord::from_str(key_record)?;
+        self.storage.update(
+            SESSIONS_COLLECTION,
+            session_id,
+            |session: &mut Option<BsmsSession>| {
+                let session = session.as_mut().ok_or_else(|| {
+                    Error::new(&format!("bsms session not found: {}", session_id))
+                })?;
+                session.add_key_record(key_record)?;
+                Ok(BsmsSessionStatus::from(&*session))
+            },
+        )
+    }
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill(bytes.as_mut_slice());
+    bytes.to_hex()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use bitcoin::secp256k1::{Message, Secp256k1};
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey};
+    use bitcoin::util::misc::{signed_msg_hash, MessageSignature};
+    use rocket::serde::json::serde_json;
+
+    const DERIVATION_PATH: &str = "m/48'/1'/0'/2'";
+
+    fn signer_xprv(seed: u8) -> ExtendedPrivKey {
+        let secp = Secp256k1::new();
+        ExtendedPrivKey::new_master(Network::Testnet, &[seed; 32])
+            .unwrap()
+            .derive_priv(&secp, &DerivationPath::from_str(DERIVATION_PATH).unwrap())
+            .unwrap()
+    }
+
+    fn signed_key_record(seed: u8, token: &str) -> String {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Testnet, &[seed; 32]).unwrap();
+        let xprv = signer_xprv(seed);
+        let key = format!(
+            "[{}/48'/1'/0'/2']{}",
+            master.fingerprint(&secp),
+            ExtendedPubKey::from_priv(&secp, &xprv)
+        );
+        let message = format!("{}\n{}\n{}\nSigner {}", BSMS_VERSION, token, key, seed);
+        let signature = secp.sign_ecdsa_recoverable(
+            &Message::from_slice(&signed_msg_hash(&message)).unwrap(),
+            &xprv.private_key,
+        );
+        format!(
+            "{}\n{}",
+            message,
+            base64::encode(MessageSignature::new(signature, true).serialize())
+        )
+    }
+
+    #[test]
+    fn test_key_record_verify() {
+        let key_record = KeyRecord::from_str(&signed_key_record(1, "a1b2c3d4e5f60708")).unwrap();
+        let cosigner = key_record.verify().unwrap();
+        assert_eq!(cosigner.derivation_path, Some(DERIVATION_PATH.to_string()));
+        assert!(util::is_testnet_multisig_xpub(&cosigner.xpub));
+    }
+
+    #[test]
+    #[should_panic(expected = "key record signature was not generated by the key")]
+    fn test_key_record_verify_should_fail_for_tampered_record() {
+        let mut key_record =
+            KeyRecord::from_str(&signed_key_record(1, "a1b2c3d4e5f60708")).unwrap();
+        key_record.description = "Tampered".to_string();
+        key_record.verify().unwrap();
+    }
+
+    #[test]
+    fn test_session_descriptor_record() {
+        let mut session = BsmsSession::new(2, 2).unwrap();
+        assert_eq!(session.tokens.len(), 2);
+        let tokens = session.tokens.clone();
+        session
+            .add_key_record(KeyRecord::from_str(&signed_key_record(1, &tokens[0])).unwrap())
+            .unwrap();
+        assert!(!session.is_complete());
+        assert!(session.multisig().is_err());
+        session
+            .add_key_record(KeyRecord::from_str(&signed_key_record(2, &tokens[1])).unwrap())
+            .unwrap();
+        let multisig = session.multisig().unwrap();
+        assert_eq!(multisig.threshold, 2);
+        assert_eq!(multisig.cosigners.len(), 2);
+
+        let record = descriptor_record(&multisig, Network::Testnet).unwrap();
+        let lines: Vec<&str> = record.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], BSMS_VERSION);
+        assert!(lines[1].starts_with("wsh(sortedmulti(2,"));
+        assert!(lines[1].ends_with("/**))"));
+        assert_eq!(lines[2], "/0/*,/1/*");
+        assert!(lines[3].starts_with("tb1q"));
+    }
+
+    #[test]
+    fn test_coordinator_add_key_record() {
+        let path = std::env::temp_dir().join(format!("bdk-services-bsms-{}", std::process::id()));
+        let storage = Storage::new(path.to_str().unwrap());
+        let coordinator = Coordinator::new(&storage);
+        let created = coordinator.create_session(1, 2).unwrap();
+        assert_eq!(created.tokens.len(), 2);
+        let status = coordinator
+            .add_key_record(&created.id, &signed_key_record(1, &created.tokens[1]))
+            .unwrap();
+        assert_eq!(
+            status,
+            BsmsSessionStatus {
+                id: created.id.clone(),
+                threshold: 1,
+                signers: 2,
+                received_key_records: 1,
+                complete: false,
+            }
+        );
+        let serialized = serde_json::to_string(&status).unwrap();
+        assert!(!serialized.contains(&created.tokens[0]));
+        assert!(!serialized.contains(&created.tokens[1]));
+        let status = coordinator
+            .add_key_record(&created.id, &signed_key_record(2, &created.tokens[0]))
+            .unwrap();
+        assert!(status.complete);
+        assert!(coordinator
+            .add_key_record("unknown", &signed_key_record(3, &created.tokens[0]))
+            .is_err());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown token")]
+    fn test_session_should_fail_for_unknown_token() {
+        let mut session = BsmsSession::new(1, 1).unwrap();
+        session
+            .add_key_record(KeyRecord::from_str(&signed_key_record(1, "a1b2c3d4e5f60708")).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "key already added to the session")]
+    fn test_session_should_fail_for_duplicated_key() {
+        let mut session = BsmsSession::new(2, 2).unwrap();
+        let tokens = session.tokens.clone();
+        session
+            .add_key_record(KeyRecord::from_str(&signed_key_record(1, &tokens[0])).unwrap())
+            .unwrap();
+        session
+            .add_key_record(KeyRecord::from_str(&signed_key_record(1, &tokens[1])).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid multisig policy: 3 of 2")]
+    fn test_session_should_fail_for_threshold_greater_than_signers() {
+        BsmsSession::new(3, 2).unwrap();
+    }
+}
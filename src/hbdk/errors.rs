@@ -1,3 +1,4 @@
+use bdk_reserves::reserves::ProofError;
 use bitcoin::consensus::encode;
 use bitcoin::locktime;
 use bitcoin::util::address;
@@ -5,7 +6,10 @@ use bitcoin::util::psbt;
 use rocket::request::Request;
 use rocket::response;
 use rocket::response::{status, Responder};
-use rocket::serde::{json::Json, Serialize};
+use rocket::serde::{
+    json::{serde_json, Json},
+    Serialize,
+};
 use std::error;
 use std::fmt;
 use xyzpub;
@@ -13,12 +17,117 @@ use xyzpub;
 #[derive(Debug, Serialize)]
 pub struct Error {
     details: String,
+    /// Typed reason of the failure, only set for proof of reserves failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<ProofFailure>,
 }
 
 impl Error {
     pub fn new(msg: &str) -> Error {
         Error {
             details: msg.to_string(),
+            reason: None,
+        }
+    }
+
+    pub fn with_reason(msg: &str, reason: ProofFailure) -> Error {
+        Error {
+            details: msg.to_string(),
+            reason: Some(reason),
+        }
+    }
+
+    pub fn reason(&self) -> Option<&ProofFailure> {
+        self.reason.as_ref()
+    }
+}
+
+/// Reason of a proof of reserves verification failure
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum ProofFailure {
+    /// The challenge input does not commit to the message
+    WrongMessage,
+    WrongNumberOfInputs,
+    WrongNumberOfOutputs,
+    /// The input does not spend an output of the wallet that is unspent
+    NonSpendableInput {
+        input_index: usize,
+    },
+    MissingSignature {
+        input_index: usize,
+    },
+    UnsupportedSighashType {
+        input_index: usize,
+    },
+    NeitherWitnessNorLegacy {
+        input_index: usize,
+    },
+    InvalidSignature {
+        input_index: usize,
+        details: String,
+    },
+    /// The output is not the unspendable output of the proof
+    InvalidOutput,
+    /// The inputs and the output values differ, i.e. the proof pays a fee
+    InAndOutValueNotEqual,
+    OutpointNotFound {
+        input_index: usize,
+    },
+    MissingConfirmationInfo,
+    /// The proof could not be verified because of a wallet or backend error
+    WalletError {
+        details: String,
+    },
+}
+
+impl fmt::Display for ProofFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProofFailure::WrongMessage => {
+                write!(f, "the challenge input does not match the message")
+            }
+            ProofFailure::WrongNumberOfInputs => write!(f, "the proof has less than two inputs"),
+            ProofFailure::WrongNumberOfOutputs => {
+                write!(f, "the proof must have exactly one output")
+            }
+            ProofFailure::NonSpendableInput { input_index } => {
+                write!(
+                    f,
+                    "input: {} is not a spendable output of the wallet",
+                    input_index
+                )
+            }
+            ProofFailure::MissingSignature { input_index } => {
+                write!(f, "input: {} is not signed", input_index)
+            }
+            ProofFailure::UnsupportedSighashType { input_index } => {
+                write!(f, "input: {} has an unsupported sighash type", input_index)
+            }
+            ProofFailure::NeitherWitnessNorLegacy { input_index } => {
+                write!(f, "input: {} is neither witness nor legacy", input_index)
+            }
+            ProofFailure::InvalidSignature {
+                input_index,
+                details,
+            } => write!(
+                f,
+                "invalid signature of input: {}, {}",
+                input_index, details
+            ),
+            ProofFailure::InvalidOutput => {
+                write!(f, "the output is not the unspendable proof output")
+            }
+            ProofFailure::InAndOutValueNotEqual => {
+                write!(f, "the input and output values are not equal")
+            }
+            ProofFailure::OutpointNotFound { input_index } => {
+                write!(f, "output spent by input: {} not found", input_index)
+            }
+            ProofFailure::MissingConfirmationInfo => {
+                write!(f, "missing confirmation info of a proof output")
+            }
+            ProofFailure::WalletError { details } => write!(f, "{}", details),
         }
     }
 }
@@ -118,9 +227,59 @@ impl From<bdk::miniscript::descriptor::DescriptorKeyParseError> for Error {
     }
 }
 
-impl From<bdk_reserves::reserves::ProofError> for Error {
-    fn from(err: bdk_reserves::reserves::ProofError) -> Self {
-        Error::new(&format!("ProofError: {:?}", err))
+impl From<ProofError> for Error {
+    fn from(err: ProofError) -> Self {
+        let reason = match err {
+            ProofError::WrongNumberOfInputs => ProofFailure::WrongNumberOfInputs,
+            ProofError::WrongNumberOfOutputs => ProofFailure::WrongNumberOfOutputs,
+            ProofError::ChallengeInputMismatch => ProofFailure::WrongMessage,
+            ProofError::NonSpendableInput(input_index) => {
+                ProofFailure::NonSpendableInput { input_index }
+            }
+            ProofError::NotSignedInput(input_index) => {
+                ProofFailure::MissingSignature { input_index }
+            }
+            ProofError::UnsupportedSighashType(input_index) => {
+                ProofFailure::UnsupportedSighashType { input_index }
+            }
+            ProofError::NeitherWitnessNorLegacy(input_index) => {
+                ProofFailure::NeitherWitnessNorLegacy { input_index }
+            }
+            ProofError::SignatureValidation(input_index, details) => {
+                ProofFailure::InvalidSignature {
+                    input_index,
+                    details,
+                }
+            }
+            ProofError::InvalidOutput => ProofFailure::InvalidOutput,
+            ProofError::InAndOutValueNotEqual => ProofFailure::InAndOutValueNotEqual,
+            ProofError::OutpointNotFound(input_index) => {
+                ProofFailure::OutpointNotFound { input_index }
+            }
+            ProofError::MissingConfirmationInfo => ProofFailure::MissingConfirmationInfo,
+            ProofError::BdkError(err) => ProofFailure::WalletError {
+                details: err.to_string(),
+            },
+        };
+        Error::with_reason(&format!("proof of reserves error, {}", reason), reason)
+    }
+}
+
+impl From<bdk::miniscript::Error> for Error {
+    fn from(err: bdk::miniscript::Error) -> Self {
+        Error::new(&err.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::new(&err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::new(&err.to_string())
     }
 }
 
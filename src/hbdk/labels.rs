@@ -0,0 +1,301 @@
+//! Wallet labels in the BIP-329 format
+//!
+//! The labels of a wallet are kept in the local storage under the checksum of its external
+//! descriptor, and are imported and exported as JSON lines so that they can be moved between
+//! this service and wallets like Sparrow.
+
+use crate::hbdk::errors::Error;
+use crate::hbdk::storage::Storage;
+use bitcoin::{OutPoint, Txid};
+use core::fmt;
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const LABELS_COLLECTION: &str = "labels";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelType {
+    Tx,
+    Addr,
+    Pubkey,
+    Input,
+    Output,
+    Xpub,
+}
+
+impl fmt::Display for LabelType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label_type = match self {
+            LabelType::Tx => "tx",
+            LabelType::Addr => "addr",
+            LabelType::Pubkey => "pubkey",
+            LabelType::Input => "input",
+            LabelType::Output => "output",
+            LabelType::Xpub => "xpub",
+        };
+        write!(f, "{}", label_type)
+    }
+}
+
+/// BIP-329 label record
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    #[serde(rename = "type")]
+    pub label_type: LabelType,
+    /// Txid, address, public key, outpoint (txid:vout) or xpub, depending on the label type
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Key origin of the descriptor the reference belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Only used for outputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+}
+
+/// Labels of a wallet by type and reference
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Labels {
+    labels: BTreeMap<String, Label>,
+}
+
+impl Labels {
+    fn key(label_type: LabelType, reference: &str) -> String {
+        format!("{}:{}", label_type, reference)
+    }
+
+    pub fn get(&self, label_type: LabelType, reference: &str) -> Option<&Label> {
+        self.labels.get(&Self::key(label_type, reference))
+    }
+
+    pub fn get_label(&self, label_type: LabelType, reference: &str) -> Option<String> {
+        self.get(label_type, reference)
+            .and_then(|label| label.label.clone())
+    }
+
+    pub fn tx_label(&self, txid: &Txid) -> Option<String> {
+        self.get_label(LabelType::Tx, &txid.to_string())
+    }
+
+    /// Returns the label of the output, or the label of its address if the output has none
+    pub fn output_label(&self, outpoint: &OutPoint, address: Option<&str>) -> Option<String> {
+        self.get_label(LabelType::Output, &outpoint.to_string())
+            .or_else(|| address.and_then(|address| self.get_label(LabelType::Addr, address)))
+    }
+
+    /// Adds the label, replacing the existing label for the same type and reference
+    pub fn insert(&mut self, label: Label) {
+        self.labels
+            .insert(Self::key(label.label_type, &label.reference), label);
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Parses BIP-329 JSON lines, empty lines are ignored
+    pub fn parse_jsonl(content: &str) -> Result<Vec<Label>, Error> {
+        let mut labels = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let label: Label = serde_json::from_str(line)
+                .map_err(|err| Error::new(&format!("invalid label at line: {}, {}", i + 1, err)))?;
+            if label.reference.is_empty() {
+                return Err(Error::new(&format!(
+                    "label at line: {} is missing its reference",
+                    i + 1
+                )));
+            }
+            labels.push(label);
+        }
+        Ok(labels)
+    }
+
+    /// Returns the labels as BIP-329 JSON lines
+    pub fn to_jsonl(&self) -> Result<String, Error> {
+        let mut jsonl = String::new();
+        for label in self.labels.values() {
+            jsonl.push_str(&serde_json::to_string(label)?);
+            jsonl.push('\n');
+        }
+        Ok(jsonl)
+    }
+}
+
+/// Stores the labels of each wallet, keyed by the checksum of the wallet descriptor
+pub struct LabelStore<'a> {
+    storage: &'a Storage,
+}
+
+impl<'a> LabelStore<'a> {
+    pub fn new(storage: &'a Storage) -> Self {
+        LabelStore { storage }
+    }
+
+    pub fn load(&self, descriptor_checksum: &str) -> Result<Labels, Error> {
+        Ok(self
+            .storage
+            .load(LABELS_COLLECTION, descriptor_checksum)?
+            .unwrap_or_default())
+    }
+
+    /// Imports the BIP-329 JSON lines into the labels of the wallet, returns the number of
+    /// imported labels. The labels of the wallet are locked during the import so that concurrent
+    /// imports are not lost
+    pub fn import(&self, descriptor_checksum: &str, content: &str) -> Result<usize, Error> {
+        let imported = Labels::parse_jsonl(content)?;
+        let count = imported.len();
+        self.storage.update(
+            LABELS_COLLECTION,
+            descriptor_checksum,
+            |labels: &mut Option<Labels>| {
+                let labels = labels.get_or_insert_with(Labels::default);
+                for label in imported {
+                    labels.insert(label);
+                }
+                Ok(count)
+            },
+        )
+    }
+
+    pub fn export(&self, descriptor_checksum: &str) -> Result<String, Error> {
+        self.load(descriptor_checksum)?.to_jsonl()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use core::str::FromStr;
+    use std::sync::Arc;
+
+    const JSONL: &str = r#"{"type":"tx","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd","label":"Transaction","origin":"wpkh([d0ff4a2c/84'/1'/0'])"}
+{"type":"addr","ref":"tb1q6xhxcrzmjwf6ce5jlj08gyrmu4eq3zwpv0ss3a","label":"Address"}
+
+{"type":"output","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1","label":"Output","spendable":false}
+{"type":"xpub","ref":"tpubD6NzVbkrYhZ4WM6TPnKjDCA5Nsm8sHEbgD6iRGAD6z5t4pZP2pddFgUhZDwWGCbwmtCnAcPP6JDzA8qwLMTBALMf7ZbM7wwu2nKMFpRbmzJ","label":"Cold Storage"}
+"#;
+
+    #[test]
+    fn test_labels_parse_jsonl() {
+        let labels = Labels::parse_jsonl(JSONL).unwrap();
+        assert_eq!(labels.len(), 4);
+        assert_eq!(labels[0].label_type, LabelType::Tx);
+        assert_eq!(
+            labels[0].origin,
+            Some("wpkh([d0ff4a2c/84'/1'/0'])".to_string())
+        );
+        assert_eq!(labels[2].spendable, Some(false));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid label at line: 2")]
+    fn test_labels_parse_jsonl_should_fail_for_unknown_type() {
+        Labels::parse_jsonl(
+            "{\"type\":\"tx\",\"ref\":\"a\",\"label\":\"a\"}\n{\"type\":\"utxo\",\"ref\":\"b\"}",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_labels_lookup() {
+        let mut labels = Labels::default();
+        for label in Labels::parse_jsonl(JSONL).unwrap() {
+            labels.insert(label);
+        }
+        let txid =
+            Txid::from_str("f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd")
+                .unwrap();
+        assert_eq!(labels.tx_label(&txid), Some("Transaction".to_string()));
+        assert_eq!(
+            labels.output_label(&OutPoint::new(txid, 1), None),
+            Some("Output".to_string())
+        );
+        assert_eq!(
+            labels.output_label(
+                &OutPoint::new(txid, 0),
+                Some("tb1q6xhxcrzmjwf6ce5jlj08gyrmu4eq3zwpv0ss3a")
+            ),
+            Some("Address".to_string())
+        );
+        assert_eq!(labels.output_label(&OutPoint::new(txid, 0), None), None);
+    }
+
+    #[test]
+    fn test_label_store_import_and_export() {
+        let path = std::env::temp_dir().join(format!("bdk-services-labels-{}", std::process::id()));
+        let storage = Storage::new(path.to_str().unwrap());
+        let label_store = LabelStore::new(&storage);
+        assert!(label_store.load("aakuctju").unwrap().is_empty());
+        assert_eq!(label_store.import("aakuctju", JSONL).unwrap(), 4);
+        assert_eq!(
+            label_store
+                .import(
+                    "aakuctju",
+                    "{\"type\":\"addr\",\"ref\":\"tb1q6xhxcrzmjwf6ce5jlj08gyrmu4eq3zwpv0ss3a\",\"label\":\"Renamed\"}"
+                )
+                .unwrap(),
+            1
+        );
+        let labels = label_store.load("aakuctju").unwrap();
+        assert_eq!(labels.len(), 4);
+        assert_eq!(
+            labels.get_label(
+                LabelType::Addr,
+                "tb1q6xhxcrzmjwf6ce5jlj08gyrmu4eq3zwpv0ss3a"
+            ),
+            Some("Renamed".to_string())
+        );
+
+        let exported = label_store.export("aakuctju").unwrap();
+        assert_eq!(exported.lines().count(), 4);
+        let reimported = Labels::parse_jsonl(&exported).unwrap();
+        assert!(reimported.iter().all(|label| labels
+            .get(label.label_type, &label.reference)
+            .map(|existing| existing == label)
+            .unwrap_or(false)));
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_label_store_concurrent_imports() {
+        let path = std::env::temp_dir().join(format!(
+            "bdk-services-labels-concurrent-{}",
+            std::process::id()
+        ));
+        let storage = Arc::new(Storage::new(path.to_str().unwrap()));
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    let label_store = LabelStore::new(&storage);
+                    for j in 0..10 {
+                        let label = format!(
+                            "{{\"type\":\"tx\",\"ref\":\"{}-{}\",\"label\":\"Label\"}}",
+                            i, j
+                        );
+                        label_store.import("aakuctju", &label).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let labels = LabelStore::new(&storage).load("aakuctju").unwrap();
+        assert_eq!(labels.len(), 80);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
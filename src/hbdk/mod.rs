@@ -1,33 +1,62 @@
+pub mod bip322;
+pub mod bsms;
 pub mod errors;
+pub mod labels;
+pub mod reserves;
+pub mod storage;
 pub mod util;
+pub mod wallet_config;
 
-use bdk::blockchain::{Blockchain as BlockchainTrait, ElectrumBlockchain};
+use bdk::blockchain::{
+    Blockchain as BlockchainTrait, ConfigurableBlockchain, ElectrumBlockchain,
+    ElectrumBlockchainConfig, GetBlockHash, GetHeight,
+};
 use bdk::database::{Database, MemoryDatabase};
 use bdk::descriptor::{Descriptor, DescriptorPublicKey};
-use bdk::electrum_client::Client;
+use bdk::electrum_client::ElectrumApi;
 use bdk::miniscript::descriptor::WshInner;
+use bdk::miniscript::ForEachKey;
 use bdk::wallet::{AddressIndex, AddressInfo};
 use bdk::{BlockTime, FeeRate, KeychainKind, SignOptions, SyncOptions, TransactionDetails};
-use bdk_reserves::reserves::ProofOfReserves as ProofOfReservesTrait;
-use bitcoin::blockdata::{script::Script, transaction::OutPoint};
+use bdk_reserves::reserves::{verify_proof, ProofOfReserves as ProofOfReservesTrait};
+use bitcoin::blockdata::opcodes::{all::OP_CHECKMULTISIG, Class, ClassifyContext};
+use bitcoin::blockdata::{
+    script::{Instruction, Script},
+    transaction::OutPoint,
+};
+use bitcoin::hashes::{sha256, Hash as _, HashEngine, Hmac, HmacEngine};
+use bitcoin_hashes::hex::ToHex;
 use bitcoin::util::address::Address;
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint};
 use bitcoin::util::psbt::PartiallySignedTransaction;
-use bitcoin::{consensus, psbt, LockTime, Sequence};
+use bitcoin::{consensus, psbt, BlockHash, LockTime, Sequence, Witness};
 use bitcoin::{
     hash_types::Txid,
     secp256k1::{All, Secp256k1},
-    Network, Transaction,
+    Network, Transaction, TxOut,
+};
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    str::FromStr,
 };
-use core::{ops::Deref, str::FromStr};
 use errors::Error;
 use lazy_static::lazy_static;
 
 use miniscript::interpreter::Interpreter;
 use regex::Regex;
 use rocket::serde::{Deserialize, Serialize};
-use std::{clone::Clone, collections::BTreeMap, convert::TryFrom};
+use std::{
+    clone::Clone,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    convert::TryFrom,
+    ops::Range,
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-#[derive(Debug, Deserialize, Serialize, Hash, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(try_from = "CosignerShadow")]
 pub struct Cosigner {
     pub xfp: Option<String>,
@@ -40,16 +69,118 @@ pub struct CosignerShadow {
     pub xfp: Option<String>,
     pub xpub: String,
     pub derivation_path: Option<String>,
+    /// When provided, the key ownership and the key origin of the cosigner are verified
+    pub proof: Option<CosignerProof>,
+}
+
+/// Proof of ownership of the cosigner key, a bitcoin signed message signature generated with
+/// the private key of the xpub, or of a key derived from it. The signed message commits to a
+/// challenge issued by the server and to the key with its origin, see `Cosigner::proof_message`
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CosignerProof {
+    pub challenge: String,
+    /// Base64 encoded signature
+    pub signature: String,
+    /// Unhardened derivation path of the signing key relative to the xpub, e.g. 0/0, the xpub
+    /// key is used when not provided
+    pub path: Option<String>,
+    /// Base64 encoded signature of the same message generated with the master private key of the
+    /// xfp, required when the xpub is deeper than a direct child of the master key
+    pub xfp_signature: Option<String>,
+}
+
+/// First line of the cosigner proof messages
+pub const COSIGNER_PROOF_HEADER: &str = "COSIGNER PROOF 1.0";
+/// Number of seconds a cosigner proof challenge can be used for
+pub const CHALLENGE_LIFETIME_SECS: u64 = 600;
+
+lazy_static! {
+    /// Key authenticating the challenges issued by the server, renewed on every start
+    static ref CHALLENGE_KEY: [u8; 32] = rand::random();
+    /// Challenges already used by the proof of a key, with the time they expire at
+    static ref USED_CHALLENGES: Mutex<HashMap<(String, String), u64>> = Mutex::new(HashMap::new());
+}
+
+/// Challenge the cosigner proof messages must commit to, it can only be used once for each key
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CosignerChallenge {
+    pub challenge: String,
+    /// Unix timestamp after which the challenge is no longer accepted
+    pub expires_at: u64,
+}
+
+impl CosignerChallenge {
+    pub fn new() -> Self {
+        let expires_at = unix_time() + CHALLENGE_LIFETIME_SECS;
+        let nonce: [u8; 16] = rand::random();
+        let payload = format!("{}-{}", expires_at, nonce.to_hex());
+        CosignerChallenge {
+            challenge: format!("{}-{}", payload, Self::mac(&payload)),
+            expires_at,
+        }
+    }
+
+    /// Checks that the challenge was issued by the server, has not expired and has not been used
+    /// for the key before, and marks it as used for the key
+    fn redeem(challenge: &str, key: &str) -> Result<(), Error> {
+        let invalid = || Error::new(&format!("invalid cosigner proof challenge: {}", challenge));
+        let (payload, mac) = challenge.rsplit_once('-').ok_or_else(invalid)?;
+        let expires_at: u64 = payload
+            .split_once('-')
+            .and_then(|(expires_at, _)| expires_at.parse().ok())
+            .ok_or_else(invalid)?;
+        if mac != Self::mac(payload) {
+            return Err(invalid());
+        }
+        let now = unix_time();
+        if expires_at < now {
+            return Err(Error::new(&format!(
+                "cosigner proof challenge expired: {}",
+                challenge
+            )));
+        }
+        let mut used = USED_CHALLENGES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        used.retain(|_, expires_at| *expires_at >= now);
+        if used
+            .insert((challenge.to_string(), key.to_string()), expires_at)
+            .is_some()
+        {
+            return Err(Error::new(&format!(
+                "cosigner proof challenge already used for the key: {}",
+                key
+            )));
+        }
+        Ok(())
+    }
+
+    fn mac(payload: &str) -> String {
+        let mut engine = HmacEngine::<sha256::Hash>::new(&*CHALLENGE_KEY);
+        engine.input(payload.as_bytes());
+        Hmac::<sha256::Hash>::from_engine(engine).to_hex()
+    }
+}
+
+impl Default for CosignerChallenge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
 }
 
 impl Cosigner {
     pub fn descriptor(&self, change: bool) -> Result<String, Error> {
         let mut s = String::new();
         let child = if change { "/1/*" } else { "/0/*" };
-        if self.xfp.is_some() && self.derivation_path.is_some() {
-            let xfp = self.xfp.as_ref().unwrap();
-            let path = self.derivation_path.as_ref().unwrap();
-            let path = path.trim_start_matches("m/").trim_start_matches("/");
+        if let (Some(xfp), Some(path)) = (&self.xfp, &self.derivation_path) {
+            let path = path.trim_start_matches("m/").trim_start_matches('/');
             s = format!("[{}/{}]", xfp, path);
         }
         if !util::is_multisig_xpub(&self.xpub) {
@@ -69,12 +200,176 @@ impl Cosigner {
         Ok(DescriptorPublicKey::from_str(&self.descriptor(change)?)?)
     }
 
+    /// Checks that the depth, child number and parent fingerprint of the xpub are consistent with
+    /// the xfp and derivation path of the cosigner
+    pub fn verify_key_origin(&self) -> Result<(), Error> {
+        let (xfp, derivation_path) = match (&self.xfp, &self.derivation_path) {
+            (Some(xfp), Some(derivation_path)) => (xfp, derivation_path),
+            _ => {
+                return Err(Error::new(&format!(
+                    "xfp and derivation path are required to verify the key origin, xpub:{}",
+                    self.xpub
+                )))
+            }
+        };
+        let xfp = Fingerprint::from_str(xfp)
+            .map_err(|_| Error::new(&format!("invalid xfp: {}", xfp)))?;
+        let derivation_path = DerivationPath::from_str(&format!(
+            "m/{}",
+            derivation_path
+                .trim_start_matches("m/")
+                .trim_start_matches('/')
+        ))?;
+        let xpub = self.extended_pub_key()?;
+        if xpub.depth as usize != derivation_path.len() {
+            return Err(Error::new(&format!(
+                "xpub depth: {} does not match the derivation path: {}",
+                xpub.depth, derivation_path
+            )));
+        }
+        match derivation_path.as_ref().last() {
+            Some(child_number) => {
+                if xpub.child_number != *child_number {
+                    return Err(Error::new(&format!(
+                        "xpub child number: {} does not match the derivation path: {}",
+                        xpub.child_number, derivation_path
+                    )));
+                }
+                // The parent fingerprint can only be checked against the xfp for keys derived
+                // directly from the master key
+                if xpub.depth == 1 && xpub.parent_fingerprint != xfp {
+                    return Err(Error::new(&format!(
+                        "xpub parent fingerprint: {} does not match the xfp: {}",
+                        xpub.parent_fingerprint, xfp
+                    )));
+                }
+            }
+            None => {
+                if xpub.fingerprint() != xfp {
+                    return Err(Error::new(&format!(
+                        "xpub fingerprint: {} does not match the xfp: {}",
+                        xpub.fingerprint(),
+                        xfp
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the message signed by the cosigner proof, it commits to the challenge and to the
+    /// key with its origin:
+    ///
+    /// COSIGNER PROOF 1.0
+    /// <challenge>
+    /// [<xfp>/<derivation path>]<xpub>
+    pub fn proof_message(&self, challenge: &str) -> Result<String, Error> {
+        Ok(format!(
+            "{}\n{}\n{}",
+            COSIGNER_PROOF_HEADER,
+            challenge,
+            self.key_with_origin()?
+        ))
+    }
+
+    /// Returns the legacy xpub with its key origin, i.e. [xfp/derivation_path]xpub
+    fn key_with_origin(&self) -> Result<String, Error> {
+        match (&self.xfp, &self.derivation_path) {
+            (Some(xfp), Some(derivation_path)) => Ok(format!(
+                "[{}/{}]{}",
+                xfp.to_lowercase(),
+                derivation_path
+                    .trim_start_matches("m/")
+                    .trim_start_matches('/'),
+                util::to_legacy_xpub(&self.xpub)?
+            )),
+            _ => Err(Error::new(&format!(
+                "xfp and derivation path are required for the key origin, xpub:{}",
+                self.xpub
+            ))),
+        }
+    }
+
+    /// Checks that the proof signature of the proof message was generated by the private key of
+    /// the xpub, or of the key derived from it with the proof path, and redeems the challenge
+    pub fn verify_ownership(&self, proof: &CosignerProof) -> Result<(), Error> {
+        let message = self.proof_message(&proof.challenge)?;
+        let mut xpub = self.extended_pub_key()?;
+        if let Some(path) = &proof.path {
+            let path = DerivationPath::from_str(&format!(
+                "m/{}",
+                path.trim_start_matches("m/").trim_start_matches('/')
+            ))?;
+            xpub = xpub.derive_pub(&Secp256k1::verification_only(), &path)?;
+        }
+        if !util::verify_message_signature(&xpub.public_key, &message, &proof.signature)? {
+            return Err(Error::new(&format!(
+                "cosigner proof signature was not generated by a key of the xpub: {}",
+                self.xpub
+            )));
+        }
+        self.verify_xfp(&message, proof)?;
+        CosignerChallenge::redeem(&proof.challenge, &self.key_with_origin()?)
+    }
+
+    /// Checks that the xfp is the fingerprint of the master key. The parent fingerprint of the
+    /// xpub only identifies the master key up to depth 1, deeper xpubs need the proof message to
+    /// be signed by the master key too
+    fn verify_xfp(&self, message: &str, proof: &CosignerProof) -> Result<(), Error> {
+        if self.extended_pub_key()?.depth <= 1 {
+            return Ok(());
+        }
+        let xfp_signature = proof.xfp_signature.as_ref().ok_or_else(|| {
+            Error::new(&format!(
+                "cosigner proof xfp signature is required for the xpub: {}",
+                self.xpub
+            ))
+        })?;
+        let master_key = util::recover_message_signer(message, xfp_signature)?;
+        let master_key_hash = bitcoin::PublicKey::new(master_key).pubkey_hash();
+        let fingerprint = Fingerprint::from(&master_key_hash[0..4]).to_string();
+        if !self.xfp_is(&fingerprint) {
+            return Err(Error::new(&format!(
+                "cosigner proof xfp signature key fingerprint: {} does not match the xfp: {}",
+                fingerprint,
+                self.xfp.as_deref().unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    fn extended_pub_key(&self) -> Result<ExtendedPubKey, Error> {
+        Ok(ExtendedPubKey::from_str(&util::to_legacy_xpub(
+            &self.xpub,
+        )?)?)
+    }
+
+    /// Returns the public key of the cosigner for the index of the receive or change keychain
+    pub fn derive_public_key(
+        &self,
+        change: bool,
+        index: u32,
+        secp_ctx: &Secp256k1<All>,
+    ) -> Result<bitcoin::PublicKey, Error> {
+        // Hardened indexes can not be derived from the xpub
+        ChildNumber::from_normal_idx(index)?;
+        Ok(self
+            .public_key_descriptor(change)?
+            .at_derivation_index(index)
+            .derive_public_key(secp_ctx)?)
+    }
+
+    /// Checks that the xpub belongs to the network
+    pub fn verify_network(&self, network: Network) -> Result<(), Error> {
+        util::verify_xpub_network(&self.xpub, network)
+    }
+
     pub fn xfp_is(&self, fp: &str) -> bool {
-        return if let Some(xfp) = &self.xfp {
+        if let Some(xfp) = &self.xfp {
             fp.to_lowercase() == xfp.to_lowercase()
         } else {
             false
-        };
+        }
     }
 }
 
@@ -87,6 +382,15 @@ impl TryFrom<CosignerShadow> for Cosigner {
             cosigner.xfp = shadow.xfp.map(|s| s.to_lowercase());
             cosigner.derivation_path = shadow.derivation_path;
         }
+        // The proof is required to trust the xfp, but the depth and child number of the xpub are
+        // checked against any key origin
+        if shadow.proof.is_some() || (cosigner.xfp.is_some() && cosigner.derivation_path.is_some())
+        {
+            cosigner.verify_key_origin()?;
+        }
+        if let Some(proof) = &shadow.proof {
+            cosigner.verify_ownership(proof)?;
+        }
         Ok(cosigner)
     }
 }
@@ -105,9 +409,7 @@ impl FromStr for Cosigner {
         if FULL_XPUB_RE.is_match(s) {
             let captures = FULL_XPUB_RE.captures(s).unwrap();
             return Ok(Cosigner {
-                xfp: Some(String::from(
-                    captures.name("xfp").unwrap().as_str().to_lowercase(),
-                )),
+                xfp: Some(captures.name("xfp").unwrap().as_str().to_lowercase()),
                 xpub: String::from(captures.name("xpub").unwrap().as_str()),
                 derivation_path: Some(format!("m{}", captures.name("dp").unwrap().as_str())),
             });
@@ -142,43 +444,68 @@ impl PartialEq for Cosigner {
 
 impl Eq for Cosigner {}
 
+impl Hash for Cosigner {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.xfp.as_ref().map(|xfp| xfp.to_lowercase()).hash(state);
+        self.derivation_path.hash(state);
+        self.xpub.hash(state);
+    }
+}
+
 pub struct CosignerFinder<'a> {
     cosigners: Vec<&'a Cosigner>,
     pub_key_cosigner_map: Option<BTreeMap<bitcoin::PublicKey, &'a Cosigner>>,
     secp_ctx: &'a Secp256k1<All>,
-    start_idx: u32,
-    end_idx: u32,
+    /// Derivation index ranges the keys are searched in, with whether they are change indexes
+    ranges: Vec<(bool, Range<u32>)>,
 }
 
 impl<'a> CosignerFinder<'a> {
     pub fn new(
-        cosigners: &'a Vec<Cosigner>,
+        cosigners: &'a [Cosigner],
+        change: bool,
         start_idx: u32,
         end_idx: u32,
         secp_ctx: &'a Secp256k1<All>,
-    ) -> Self {
-        assert!(start_idx < end_idx, "start_idx must be less than end_idx");
-        CosignerFinder {
-            cosigners: cosigners.iter().map(|cosigner| cosigner).collect(),
+    ) -> Result<Self, Error> {
+        Ok(CosignerFinder {
+            cosigners: cosigners.iter().collect(),
             pub_key_cosigner_map: None,
             secp_ctx,
-            start_idx,
-            end_idx,
+            ranges: vec![(change, Self::search_range(start_idx, end_idx)?)],
+        })
+    }
+
+    /// Searches the keys in another range of derivation indexes as well
+    pub fn with_range(mut self, change: bool, start_idx: u32, end_idx: u32) -> Result<Self, Error> {
+        self.ranges
+            .push((change, Self::search_range(start_idx, end_idx)?));
+        self.pub_key_cosigner_map = None;
+        Ok(self)
+    }
+
+    fn search_range(start_idx: u32, end_idx: u32) -> Result<Range<u32>, Error> {
+        if start_idx >= end_idx {
+            return Err(Error::new(&format!(
+                "invalid derivation index range, start index: {} must be less than end index: {}",
+                start_idx, end_idx
+            )));
         }
+        Ok(start_idx..end_idx)
     }
+
     fn generate_pub_key_cosigner_map(
         &self,
     ) -> Result<BTreeMap<bitcoin::PublicKey, &'a Cosigner>, Error> {
         let mut map = BTreeMap::new();
         for cosigner in &self.cosigners {
-            let xpub = cosigner.public_key_descriptor(true)?;
-            for i in self.start_idx..self.end_idx {
-                map.insert(
-                    xpub.clone()
-                        .at_derivation_index(i)
-                        .derive_public_key(self.secp_ctx)?,
-                    cosigner.clone(),
-                );
+            for (change, range) in &self.ranges {
+                for i in range.clone() {
+                    map.insert(
+                        cosigner.derive_public_key(*change, i, self.secp_ctx)?,
+                        *cosigner,
+                    );
+                }
             }
         }
         Ok(map)
@@ -188,15 +515,15 @@ impl<'a> CosignerFinder<'a> {
         &mut self,
         public_key: &bitcoin::PublicKey,
     ) -> Result<Option<&&'a Cosigner>, Error> {
-        if self.pub_key_cosigner_map.is_none() {
-            self.pub_key_cosigner_map = Some(self.generate_pub_key_cosigner_map()?);
-        }
-        let map = &self.pub_key_cosigner_map.as_ref().unwrap();
-        Ok(map.get(public_key))
+        let map = match self.pub_key_cosigner_map.take() {
+            Some(map) => map,
+            None => self.generate_pub_key_cosigner_map()?,
+        };
+        Ok(self.pub_key_cosigner_map.insert(map).get(public_key))
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Multisig {
     pub threshold: u32,
     pub cosigners: Vec<Cosigner>,
@@ -204,10 +531,10 @@ pub struct Multisig {
 
 impl Multisig {
     pub fn new(threshold: u32) -> Self {
-        return Multisig {
+        Multisig {
             threshold,
             cosigners: Vec::new(),
-        };
+        }
     }
 
     pub fn add_cosigner(&mut self, cosigner: Cosigner) {
@@ -220,8 +547,7 @@ impl Multisig {
                 "multisig threshold: {} is greater than the number of cosigners: {}",
                 self.threshold,
                 self.cosigners.len()
-            ))
-            .into());
+            )));
         }
         let mut descriptor = String::new();
         for cosigner in &self.cosigners {
@@ -233,6 +559,67 @@ impl Multisig {
         ))
     }
 
+    /// Returns the descriptors of the receive and change keychains, with their checksums
+    pub fn descriptors(&self) -> Result<Descriptors, Error> {
+        let descriptor = |change| -> Result<String, Error> {
+            Ok(Descriptor::<DescriptorPublicKey>::from_str(&self.descriptor(change)?)?.to_string())
+        };
+        Ok(Descriptors::new(descriptor(false)?, descriptor(true)?))
+    }
+
+    /// Returns the multisig described by the external descriptor
+    pub fn from_descriptors(descriptors: &Descriptors) -> Result<Multisig, Error> {
+        let secp = Secp256k1::signing_only();
+        let (descriptor, _) = Descriptor::parse_descriptor(&secp, &descriptors.descriptor)?;
+        Self::from_descriptor(&descriptor)
+    }
+
+    pub(crate) fn from_descriptor(
+        descriptor: &Descriptor<DescriptorPublicKey>,
+    ) -> Result<Multisig, Error> {
+        let mut multisig;
+        if let Descriptor::Wsh(wsh) = descriptor {
+            if let WshInner::SortedMulti(sm) = wsh.as_inner() {
+                multisig = Multisig::new(sm.k as u32);
+                for pk in &sm.pks {
+                    if let DescriptorPublicKey::XPub(xpub) = pk {
+                        let mut cosigner = Cosigner::from_str(
+                            &util::to_segwit_native_multisig_xpub(&xpub.xkey.to_string())?,
+                        )?;
+                        if let Some((xfp, derivation_path)) = &xpub.origin {
+                            cosigner.xfp = Some(xfp.to_string());
+                            cosigner.derivation_path = Some(derivation_path.to_string());
+                        }
+                        multisig.add_cosigner(cosigner);
+                    } else {
+                        return Err(Error::new(&format!(
+                            "Wallet does not only contain xpubs, found:{}",
+                            pk
+                        )));
+                    }
+                }
+            } else {
+                return Err(Error::new(
+                    "Wallet is not of type sorted multisig, found miniscript",
+                ));
+            }
+        } else {
+            return Err(Error::new(&format!(
+                "Wallet is not of type Pay-to-Witness-Script-Hash, found: {}",
+                descriptor
+            )));
+        }
+        Ok(multisig)
+    }
+
+    /// Checks that the xpubs of all the cosigners belong to the network
+    pub fn verify_network(&self, network: Network) -> Result<(), Error> {
+        for cosigner in &self.cosigners {
+            cosigner.verify_network(network)?;
+        }
+        Ok(())
+    }
+
     pub fn find_by_xfp(&self, xfp: &str) -> Option<&Cosigner> {
         self.cosigners.iter().find(|cosigner| cosigner.xfp_is(xfp))
     }
@@ -250,11 +637,12 @@ impl Multisig {
 
     pub fn cosigner_finder<'secp>(
         &'secp self,
+        change: bool,
         start_idx: u32,
         end_idx: u32,
         secp_ctx: &'secp Secp256k1<All>,
-    ) -> CosignerFinder {
-        CosignerFinder::new(&self.cosigners, start_idx, end_idx, secp_ctx)
+    ) -> Result<CosignerFinder<'secp>, Error> {
+        CosignerFinder::new(&self.cosigners, change, start_idx, end_idx, secp_ctx)
     }
 }
 
@@ -284,868 +672,3437 @@ impl Descriptors {
             change_descriptor: None,
         }
     }
+
+    /// Returns the checksum of the external descriptor, which identifies the wallet, without
+    /// connecting to the blockchain
+    pub fn checksum(&self) -> Result<String, Error> {
+        let secp = Secp256k1::signing_only();
+        let (descriptor, _) = Descriptor::parse_descriptor(&secp, &self.descriptor)?;
+        descriptor_checksum(&descriptor)
+    }
+
+    /// Checks that the extended keys of the descriptors belong to the network
+    pub fn verify_network(&self, network: Network) -> Result<(), Error> {
+        let secp = Secp256k1::signing_only();
+        let descriptors =
+            std::iter::once(&self.descriptor).chain(self.change_descriptor.as_ref());
+        for descriptor in descriptors {
+            let (descriptor, _) = Descriptor::parse_descriptor(&secp, descriptor)?;
+            let mut xkeys = Vec::new();
+            descriptor.for_each_key(|pk| {
+                if let DescriptorPublicKey::XPub(xpub) = pk {
+                    xkeys.push(xpub.xkey);
+                }
+                true
+            });
+            for xkey in xkeys {
+                if !util::is_same_network(xkey.network, network) {
+                    return Err(Error::new(&format!(
+                        "network mismatch, descriptor key: {} is for network: {}, expected: {}",
+                        xkey, xkey.network, network
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn descriptor_checksum(descriptor: &Descriptor<DescriptorPublicKey>) -> Result<String, Error> {
+    let descriptor = descriptor.to_string();
+    descriptor
+        .split_once('#')
+        .map(|(_, checksum)| checksum.to_string())
+        .ok_or_else(|| Error::new(&format!("descriptor has no checksum: {}", descriptor)))
+}
+
+/// Maximum number of addresses returned by a single get addresses request
+pub const MAX_ADDRESS_COUNT: u32 = 1000;
+/// Non hardened derivation indexes are lower than 2^31
+const HARDENED_INDEX: u32 = 0x80000000;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Keychain {
+    Receive,
+    Change,
+}
+
+impl Default for Keychain {
+    fn default() -> Self {
+        Keychain::Receive
+    }
+}
+
+impl From<KeychainKind> for Keychain {
+    fn from(keychain: KeychainKind) -> Self {
+        match keychain {
+            KeychainKind::External => Keychain::Receive,
+            KeychainKind::Internal => Keychain::Change,
+        }
+    }
+}
+
+impl From<Keychain> for KeychainKind {
+    fn from(keychain: Keychain) -> Self {
+        match keychain {
+            Keychain::Receive => KeychainKind::External,
+            Keychain::Change => KeychainKind::Internal,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct Trx {
+pub struct AddressesRequest {
     pub descriptors: Descriptors,
-    pub to_address: String,
-    pub amount: u64,
-    pub fee_sat_per_vb: f32,
+    #[serde(default)]
+    pub keychain: Keychain,
+    #[serde(default)]
+    pub start: u32,
+    #[serde(default = "AddressesRequest::default_count")]
+    pub count: u32,
+    /// When set, the wallet is synced and the used flag of each address is set
+    #[serde(default = "AddressesRequest::default_sync")]
+    pub sync: bool,
+}
+
+impl AddressesRequest {
+    fn default_count() -> u32 {
+        20
+    }
+
+    fn default_sync() -> bool {
+        true
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct SignedTrx {
-    pub descriptors: Descriptors,
-    pub psbts: Vec<String>,
-    pub broadcast: bool,
+pub struct DerivedAddress {
+    pub index: u32,
+    pub keychain: Keychain,
+    pub address: Address,
+    /// Whether the address has received funds, only set when the wallet was synced
+    pub used: Option<bool>,
+    pub public_keys: Vec<DerivedPublicKey>,
+    /// BIP-329 label of the address
+    pub label: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct SignedProofOfReserves {
-    pub descriptors: Descriptors,
-    pub psbts: Vec<String>,
+pub struct DerivedPublicKey {
+    pub xfp: String,
+    /// Full derivation path of the public key from the master key
+    pub derivation_path: String,
+    pub public_key: bitcoin::PublicKey,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct TrxDetails {
-    pub trx_id: Txid,
-    /// Received value (sats)
-    pub received: u64,
-    /// Sent value (sats)
-    pub sent: u64,
-    /// Fee value (sats) if available.
-    /// The availability of the fee depends on the backend. It's never `None` with an Electrum
-    /// Server backend, but it could be `None` with a Bitcoin RPC node without txindex that receive
-    /// funds while offline.
-    pub fee: Option<u64>,
-    /// If the transaction is confirmed, contains height and timestamp of the block containing the
-    /// transaction, unconfirmed transaction contains `None`.
-    pub confirmation_time: Option<BlockTime>,
+pub struct VerifyAddressRequest {
+    pub descriptors: Descriptors,
+    pub address: String,
+    /// Number of indexes of each keychain to search, starting at 0
+    #[serde(default = "VerifyAddressRequest::default_search_window")]
+    pub search_window: u32,
+}
 
-    pub inputs: Vec<TrxInput>,
-    pub outputs: Vec<TrxOutput>,
+impl VerifyAddressRequest {
+    fn default_search_window() -> u32 {
+        100
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct TrxInput {
-    previous_output_trx: OutPoint,
+pub struct AddressVerification {
+    pub address: Address,
+    pub found: bool,
+    pub keychain: Option<Keychain>,
+    pub index: Option<u32>,
+    pub script_pubkey: Option<Script>,
+    /// Public key of each cosigner derived at the index of the address
+    pub public_keys: Vec<CosignerPublicKey>,
+    /// BIP-329 label of the address
+    pub label: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct TrxOutput {
-    value: u64,
-    script_pubkey: Script,
-    address: Option<Address>,
+pub struct CosignerPublicKey {
+    pub cosigner: Cosigner,
+    pub public_key: bitcoin::PublicKey,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct ProofOfReservesRequest {
+pub struct ImportLabelsRequest {
     pub descriptors: Descriptors,
-    pub message: String,
+    /// BIP-329 JSON lines
+    pub content: String,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct ProofOfReserves {
-    pub descriptors: Descriptors,
-    pub psbt: String,
-    pub message: String,
+pub struct ImportedLabels {
+    pub descriptor_checksum: String,
+    pub imported: usize,
 }
 
-pub struct Blockchain {
-    blockchain: ElectrumBlockchain,
-    network: Network,
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ConvertXpubRequest {
+    pub xpub: String,
+    /// SLIP-132 version prefix to convert the xpub to, e.g. Zpub
+    pub version: Option<String>,
 }
 
-impl Blockchain {
-    pub fn new(url: &str, network: Network) -> Result<Self, Error> {
-        let client = Client::new(url)?;
-        return Ok(Blockchain {
-            blockchain: ElectrumBlockchain::from(client),
-            network,
-        });
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ConvertedXpub {
+    /// Details of the provided xpub
+    pub source: util::XpubDetails,
+    pub converted: Option<util::XpubDetails>,
+}
 
-    pub fn get_blockchain(&self) -> &ElectrumBlockchain {
-        &self.blockchain
+impl ConvertedXpub {
+    pub fn new(xpub: &str, version: Option<&str>) -> Result<Self, Error> {
+        Ok(ConvertedXpub {
+            source: util::get_xpub_details(xpub)?,
+            converted: version
+                .map(|version| util::convert_xpub(xpub, version))
+                .transpose()?,
+        })
     }
+}
 
-    pub fn broadcast(&self, tx: &Transaction) -> Result<(), Error> {
-        self.blockchain.broadcast(tx)?;
-        Ok(())
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ImportWalletRequest {
+    pub content: String,
+    pub format: Option<wallet_config::WalletConfigFormat>,
 }
 
-pub struct Wallet<'a> {
-    blockchain: &'a Blockchain,
-    wallet: bdk::Wallet<MemoryDatabase>,
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ImportedWallet {
+    pub name: Option<String>,
+    pub descriptors: Descriptors,
+    pub multisig: Multisig,
 }
 
-impl<'a> Wallet<'a> {
-    pub fn from_multisig(blockchain: &'a Blockchain, multisig: &Multisig) -> Result<Self, Error> {
-        Self::from_descriptors(
-            blockchain,
-            &Descriptors::new(multisig.descriptor(false)?, multisig.descriptor(true)?),
-        )
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ExportWalletRequest {
+    pub descriptors: Option<Descriptors>,
+    pub multisig: Option<Multisig>,
+    pub format: wallet_config::WalletConfigFormat,
+    pub name: Option<String>,
+}
 
-    pub fn from_descriptors(
-        blockchain: &'a Blockchain,
-        descriptors: &Descriptors,
-    ) -> Result<Self, Error> {
-        let network = blockchain.network.clone();
-        Ok(Wallet {
-            blockchain,
-            wallet: bdk::Wallet::new(
-                &descriptors.descriptor,
-                descriptors.change_descriptor.as_ref(),
-                network,
-                MemoryDatabase::default(),
-            )?,
-        })
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BsmsSessionRequest {
+    pub threshold: u32,
+    pub signers: u32,
+}
 
-    pub fn get_descriptors(&self) -> Result<Descriptors, Error> {
-        let descriptor = self.get_external_descriptor()?;
-        let change_descriptor = self.wallet.public_descriptor(KeychainKind::Internal)?;
-        let change_descriptor = change_descriptor.map(|desc| desc.to_string());
-        Ok(Descriptors {
-            descriptor: descriptor.to_string(),
-            change_descriptor,
-        })
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BsmsKeyRecordRequest {
+    pub session_id: String,
+    pub key_record: String,
+}
 
-    pub fn get_multisig(&self) -> Result<Multisig, Error> {
-        let mut multisig;
-        let descriptor = self.get_external_descriptor()?;
-        if let Descriptor::Wsh(wsh) = descriptor {
-            if let WshInner::SortedMulti(sm) = wsh.as_inner() {
-                multisig = Multisig::new(sm.k as u32);
-                for pk in &sm.pks {
-                    if let DescriptorPublicKey::XPub(xpub) = pk {
-                        let mut cosigner = Cosigner::from_str(
-                            &util::to_segwit_native_multisig_xpub(&xpub.xkey.to_string())?,
-                        )?;
-                        if let Some((xfp, derivation_path)) = &xpub.origin {
-                            cosigner.xfp = Some(xfp.to_string());
-                            cosigner.derivation_path = Some(derivation_path.to_string());
-                        }
-                        multisig.add_cosigner(cosigner);
-                    } else {
-                        return Err(Error::new(&format!(
-                            "Wallet does not only contain xpubs, found:{}",
-                            pk
-                        )));
-                    }
-                }
-            } else {
-                return Err(Error::new(
-                    "Wallet is not of type sorted multisig, found miniscript",
-                ));
-            }
-        } else {
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BsmsDescriptorRecordRequest {
+    pub session_id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BsmsDescriptorRecord {
+    pub descriptor_record: String,
+    pub descriptors: Descriptors,
+    pub multisig: Multisig,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Trx {
+    pub descriptors: Descriptors,
+    pub to_address: String,
+    pub amount: u64,
+    pub fee_sat_per_vb: f32,
+}
+
+impl Trx {
+    /// Checks that the destination address and the descriptors belong to the network
+    pub fn verify_network(&self, network: Network) -> Result<(), Error> {
+        let address = Address::from_str(&self.to_address)?;
+        if !address.is_valid_for_network(network) {
             return Err(Error::new(&format!(
-                "Wallet is not of type Pay-to-Witness-Script-Hash, found: {}",
-                descriptor
+                "network mismatch, address: {} is for network: {}, expected: {}",
+                address, address.network, network
             )));
         }
-        Ok(multisig)
+        self.descriptors.verify_network(network)
     }
+}
 
-    pub fn get_new_address(&self) -> Result<AddressInfo, Error> {
-        self.sync()?;
-        let address = self.wallet.get_address(AddressIndex::LastUnused)?;
-        Ok(address)
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignedTrx {
+    pub descriptors: Descriptors,
+    pub psbts: Vec<String>,
+    pub broadcast: bool,
+}
 
-    pub fn list_trxs(&self) -> Result<Vec<TrxDetails>, Error> {
-        self.sync()?;
-        let mut trxs: Vec<TrxDetails> = vec![];
-        let original_trxs = self.wallet.list_transactions(true)?;
-        for otrx in original_trxs {
-            let mut inputs: Vec<TrxInput> = vec![];
-            let mut outputs: Vec<TrxOutput> = vec![];
-            if let Some(rtrx) = otrx.transaction {
-                for input in rtrx.input {
-                    inputs.push(TrxInput {
-                        previous_output_trx: input.previous_output,
-                    });
-                }
-                for output in rtrx.output {
-                    outputs.push(TrxOutput {
-                        address: Some(Address::from_script(
-                            &output.script_pubkey,
-                            self.blockchain.network,
-                        )?),
-                        script_pubkey: output.script_pubkey,
-                        value: output.value,
-                    });
-                }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignedProofOfReserves {
+    pub descriptors: Descriptors,
+    pub psbts: Vec<String>,
+}
 
-                // let addr = Address::from_script(&o.script_pubkey, self.blockchain.network).unwrap();
-                // println!("address output: {}", addr);
-            }
-            trxs.push(TrxDetails {
-                trx_id: otrx.txid,
-                received: otrx.received,
-                sent: otrx.sent,
-                fee: otrx.fee,
-                confirmation_time: otrx.confirmation_time,
-                inputs: inputs,
-                outputs: outputs,
-            });
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrxDirection {
+    Incoming,
+    Outgoing,
+    /// All the outputs belong to the wallet, only the fee is spent
+    SelfTransfer,
+}
+
+impl TrxDirection {
+    pub fn of(trx: &TransactionDetails) -> Self {
+        if trx.sent == 0 || trx.received > trx.sent {
+            TrxDirection::Incoming
+        } else if trx.received + trx.fee.unwrap_or(0) == trx.sent {
+            TrxDirection::SelfTransfer
+        } else {
+            TrxDirection::Outgoing
         }
-        Ok(trxs)
     }
+}
 
-    pub fn get_balance(&self) -> Result<u64, Error> {
-        self.sync()?;
-        let balance = self.wallet.get_balance()?;
-        println!("Balance: {:?}", balance);
-        Ok(balance.get_total())
+impl fmt::Display for TrxDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self {
+            TrxDirection::Incoming => "incoming",
+            TrxDirection::Outgoing => "outgoing",
+            TrxDirection::SelfTransfer => "self-transfer",
+        };
+        write!(f, "{}", direction)
     }
+}
 
-    pub fn build_tx(
-        &self,
-        trx: &Trx,
-    ) -> Result<(PartiallySignedTransaction, TransactionDetails), Error> {
-        self.sync()?;
-        let mut builder = self.wallet.build_tx();
-        // let to_wallet = Wallet::from_descriptors(self.blockchain, &Descriptors::from_descriptor(trx.to_pub_key.clone()))?;
-        // to_wallet.sync()?;
-        builder
-            // .add_recipient(to_wallet.get_new_address()?.script_pubkey(), trx.amount)
-            .add_recipient(
-                Address::from_str(&trx.to_address)?.script_pubkey(),
-                trx.amount,
-            )
-            .enable_rbf()
-            // .do_not_spend_change()
-            .fee_rate(FeeRate::from_sat_per_vb(trx.fee_sat_per_vb));
-        Ok(builder.finish()?)
-    }
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    /// Most recent trxs first, the default
+    Desc,
+}
 
-    pub fn build_tx_encoded(&self, trx: &Trx) -> Result<String, Error> {
-        let (psbt, _) = self.build_tx(trx)?;
-        Ok(self.serialize_psbt(&psbt))
+impl Default for SortOrder {
+    fn default() -> Self {
+        SortOrder::Desc
     }
+}
 
-    pub fn get_signers(
-        &self,
-        serialized_psbt: &str,
-        search_radius: u8,
-    ) -> Result<Vec<Cosigner>, Error> {
-        let mut cosigners = Vec::new();
-        let psbt = self.deserialize_psbt(serialized_psbt)?;
-        let multisig = self.get_multisig()?;
-        for (i, input) in psbt.inputs.iter().enumerate() {
-            if !input.partial_sigs.is_empty() {
-                cosigners.append(&mut self.get_signers_from_partial_sigs(&input, &multisig)?);
-            } else if input.final_script_witness.is_some() {
-                cosigners.append(&mut self.get_signers_from_script_witness(
-                    &psbt,
-                    i,
-                    &multisig,
-                    search_radius,
-                )?);
-            }
-        }
-        Ok(cosigners)
-    }
+/// Pagination and filters of the trx history, trxs are ordered by confirmation height, with
+/// unconfirmed trxs being the most recent ones
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct TrxFilter {
+    /// Maximum number of trxs to return, all trxs are returned when not set
+    pub limit: Option<usize>,
+    /// Txid of the last trx of the previous page
+    pub cursor: Option<Txid>,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Inclusive confirmation height range, unconfirmed trxs are excluded when set
+    pub start_height: Option<u32>,
+    pub end_height: Option<u32>,
+    /// Inclusive confirmation unix timestamp range, unconfirmed trxs are excluded when set
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub direction: Option<TrxDirection>,
+    pub confirmed: Option<bool>,
+}
 
-    fn get_signers_from_partial_sigs(
-        &self,
-        input: &psbt::Input,
-        multisig: &Multisig,
-    ) -> Result<Vec<Cosigner>, Error> {
-        let mut cosigners = Vec::new();
-        for (key, _) in input.partial_sigs.iter() {
-            let keysource = input.bip32_derivation.get(&key.inner).unwrap();
-            if let Some(cosigner) = multisig.find_by_xfp(&keysource.0.to_string()) {
-                cosigners.push((*cosigner).clone());
-            } else {
-                return Err(Error::new(&format!(
-                    "no cosigner found with finger print: {}",
-                    &keysource.0.to_string()
-                )));
+impl TrxFilter {
+    pub fn matches(&self, trx: &TransactionDetails) -> bool {
+        if let Some(direction) = self.direction {
+            if TrxDirection::of(trx) != direction {
+                return false;
             }
         }
-        Ok(cosigners)
-    }
-
-    fn get_signers_from_script_witness(
-        &self,
-        psbt: &PartiallySignedTransaction,
-        input_index: usize,
-        multisig: &Multisig,
-        search_radius: u8,
-    ) -> Result<Vec<Cosigner>, Error> {
-        self.sync()?;
-        let mut cosigners = Vec::new();
-        let index = self
-            .get_last_derivation_index(KeychainKind::Internal)?
-            .unwrap_or(0);
-        let start_idx = std::cmp::max(0, index as i32 - search_radius as i32) as u32;
-        let end_idx = index + search_radius as u32;
-        let interpreter = self.get_tx_interpreter(psbt, input_index)?;
-        let mut finder = multisig.cosigner_finder(start_idx, end_idx, self.wallet.secp_ctx());
-        for elem in interpreter.iter_assume_sigs() {
-            match elem.expect("no evaluation error") {
-                miniscript::interpreter::SatisfiedConstraint::PublicKey { key_sig } => {
-                    let (key, _) = key_sig
-                        .as_ecdsa()
-                        .expect("expected ecdsa sig, found schnorr sig");
-                    println!("Signed with:\n key: {}\n", key);
-                    let cosigner = finder.find_by_public_key(&key)?.ok_or(Error::new(&format!(
-                        "no cosigner found for public key: {}",
-                        key
-                    )))?;
-                    cosigners.push((*cosigner).clone());
-                }
-                _ => {}
+        if let Some(confirmed) = self.confirmed {
+            if trx.confirmation_time.is_some() != confirmed {
+                return false;
             }
         }
-        Ok(cosigners)
-    }
-
-    fn get_last_derivation_index(&self, keychain: KeychainKind) -> Result<Option<u32>, bdk::Error> {
-        self.wallet.database().deref().get_last_index(keychain)
-    }
-
-    fn get_tx_interpreter<'psbt>(
-        &'psbt self,
-        psbt: &'psbt PartiallySignedTransaction,
-        input_index: usize,
-    ) -> Result<Interpreter, Error> {
-        let script_sig = &psbt.unsigned_tx.input[input_index].script_sig;
-        let input = &psbt.inputs[input_index];
-        if let Some(witness_utxo) = &input.witness_utxo {
-            if let Some(script_witness) = &input.final_script_witness {
-                Ok(Interpreter::from_txdata(
-                    &witness_utxo.script_pubkey,
-                    &script_sig,
-                    &script_witness,
-                    Sequence(0),
-                    LockTime::from_time(0)?,
-                )?)
-            } else {
-                Err(Error::new(&format!(
-                    "failed to create the tx interpreter, final script witness not found"
-                )))
+        let has_range = self.start_height.is_some()
+            || self.end_height.is_some()
+            || self.start_time.is_some()
+            || self.end_time.is_some();
+        match &trx.confirmation_time {
+            Some(time) => {
+                self.start_height.map_or(true, |start| time.height >= start)
+                    && self.end_height.map_or(true, |end| time.height <= end)
+                    && self
+                        .start_time
+                        .map_or(true, |start| time.timestamp >= start)
+                    && self.end_time.map_or(true, |end| time.timestamp <= end)
             }
-        } else {
-            Err(Error::new(&format!(
-                "failed to create the tx interpreter, witness utxo not found"
-            )))
+            None => !has_range,
         }
     }
 
-    pub fn finalize_trx(&self, psbts: &[String], broadcast: bool) -> Result<String, Error> {
-        let tx = self.finalize_psbt(psbts)?.extract_tx();
-        if broadcast {
-            self.blockchain.broadcast(&tx)?;
-        }
-        Ok(tx.txid().to_string())
-    }
-
-    fn finalize_psbt(&self, psbts: &[String]) -> Result<PartiallySignedTransaction, Error> {
-        if psbts.len() < 1 {
-            return Err(Error::new(&format!(
-                "failed to finalized psbt, there are less than required psbts, found: {}",
-                psbts.len()
-            )));
-        }
-        let mut combined = self.deserialize_psbt(&psbts[0])?;
-        for psbt in &psbts[1..] {
-            combined.combine(self.deserialize_psbt(psbt)?)?;
-        }
-        self.sync()?;
-        let finalized = self
-            .wallet
-            .finalize_psbt(&mut combined, SignOptions::default())?;
-        if !finalized {
-            return Err(Error::new("provided psbts do not finalize tx"));
+    /// Filters, orders and paginates the trxs
+    pub fn apply(
+        &self,
+        trxs: Vec<TransactionDetails>,
+    ) -> Result<Vec<TransactionDetails>, Error> {
+        let mut trxs: Vec<TransactionDetails> =
+            trxs.into_iter().filter(|trx| self.matches(trx)).collect();
+        // Unconfirmed trxs are the most recent ones
+        trxs.sort_by_key(|trx| {
+            (
+                trx.confirmation_time
+                    .as_ref()
+                    .map_or(u32::MAX, |time| time.height),
+                trx.txid,
+            )
+        });
+        if self.order == SortOrder::Desc {
+            trxs.reverse();
         }
-        Ok(combined)
+        let start = match &self.cursor {
+            Some(cursor) => {
+                trxs.iter()
+                    .position(|trx| trx.txid == *cursor)
+                    .ok_or_else(|| Error::new(&format!("cursor trx not found: {}", cursor)))?
+                    + 1
+            }
+            None => 0,
+        };
+        Ok(trxs
+            .into_iter()
+            .skip(start)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect())
     }
+}
 
-    pub fn create_proof_of_reserves_encoded(&self, message: &str) -> Result<String, Error> {
-        Ok(self.serialize_psbt(&self.create_proof_of_reserves(message)?))
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ListTrxsRequest {
+    #[serde(flatten)]
+    pub descriptors: Descriptors,
+    #[serde(flatten)]
+    pub filter: TrxFilter,
+}
 
-    pub fn create_proof_of_reserves(
-        &self,
-        message: &str,
-    ) -> Result<PartiallySignedTransaction, Error> {
-        self.sync()?;
-        Ok(self.wallet.create_proof(message)?)
-    }
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ExportTrxsRequest {
+    #[serde(flatten)]
+    pub descriptors: Descriptors,
+    /// Inclusive confirmation unix timestamp range, unconfirmed trxs are excluded when set
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+}
 
-    pub fn finalize_proof_of_reserves(&self, psbts: &[String]) -> Result<String, Error> {
-        if psbts.len() < 1 {
-            return Err(Error::new(&format!(
-                "failed to finalized psbt, there are less than required psbts, found: {}",
-                psbts.len()
-            )));
+const TRXS_CSV_HEADER: &str = "date,txid,direction,amount,fee,balance,height,label";
+
+/// Returns the trxs in the time range as CSV lines, the header first, with the balance of the
+/// wallet after each trx. The trxs must be the whole history of the wallet in ascending order, so
+/// that the running balance includes the trxs before the range. The amount excludes the fee, which
+/// has its own column, so the balance changes by the amount minus the fee.
+fn trxs_to_csv(
+    trxs: Vec<TrxDetails>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+) -> impl Iterator<Item = String> {
+    let has_range = start_time.is_some() || end_time.is_some();
+    let rows = trxs
+        .into_iter()
+        .scan(0_i64, |balance, trx| {
+            *balance += trx.net;
+            Some((*balance, trx))
+        })
+        .filter(move |(_, trx)| match &trx.confirmation_time {
+            Some(time) => {
+                start_time.map_or(true, |start| time.timestamp >= start)
+                    && end_time.map_or(true, |end| time.timestamp <= end)
+            }
+            None => !has_range,
+        })
+        .map(|(balance, trx)| trx_to_csv_row(&trx, balance));
+    std::iter::once(format!("{}\n", TRXS_CSV_HEADER)).chain(rows)
+}
+
+fn trx_to_csv_row(trx: &TrxDetails, balance: i64) -> String {
+    // The fee is only paid by the wallet for the trxs it sends
+    let fee = match trx.direction {
+        TrxDirection::Incoming => None,
+        _ => trx.fee,
+    };
+    let amount = trx.net + fee.unwrap_or(0) as i64;
+    format!(
+        "{},{},{},{},{},{},{},{}\n",
+        trx.confirmation_time
+            .as_ref()
+            .map(|time| util::format_timestamp(time.timestamp))
+            .unwrap_or_default(),
+        trx.trx_id,
+        trx.direction,
+        amount,
+        fee.map(|fee| fee.to_string()).unwrap_or_default(),
+        balance,
+        trx.confirmation_time
+            .as_ref()
+            .map(|time| time.height.to_string())
+            .unwrap_or_default(),
+        trx.label
+            .as_deref()
+            .map(util::csv_field)
+            .unwrap_or_default(),
+    )
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TrxDetails {
+    pub trx_id: Txid,
+    /// Received value (sats)
+    pub received: u64,
+    /// Sent value (sats)
+    pub sent: u64,
+    /// Fee value (sats) if available.
+    /// The availability of the fee depends on the backend. It's never `None` with an Electrum
+    /// Server backend, but it could be `None` with a Bitcoin RPC node without txindex that receive
+    /// funds while offline.
+    pub fee: Option<u64>,
+    /// If the transaction is confirmed, contains height and timestamp of the block containing the
+    /// transaction, unconfirmed transaction contains `None`.
+    pub confirmation_time: Option<BlockTime>,
+
+    pub inputs: Vec<TrxInput>,
+    pub outputs: Vec<TrxOutput>,
+    /// BIP-329 label of the transaction
+    pub label: Option<String>,
+    /// Received minus sent value (sats)
+    pub net: i64,
+    pub direction: TrxDirection,
+    /// Number of blocks from the tip of the chain to the block containing the transaction, 0 for
+    /// unconfirmed transactions
+    pub confirmations: u32,
+    /// Virtual size (vbytes), only available when the raw transaction is known
+    pub vsize: Option<usize>,
+    /// Fee rate (sats/vbyte)
+    pub feerate: Option<f32>,
+    /// Whether any input signals replaceability, as defined by BIP-125
+    pub rbf_signalled: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GetTrxRequest {
+    pub descriptors: Descriptors,
+    pub txid: Txid,
+}
+
+/// Trx details with the raw trx and the signers of its inputs
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TrxWithSigners {
+    #[serde(flatten)]
+    pub details: TrxDetails,
+    /// Consensus encoded trx (hex)
+    pub hex: String,
+    /// Hash of the block containing the trx, unconfirmed trxs contain `None`
+    pub block_hash: Option<BlockHash>,
+    /// Signers of the inputs spending outputs of the wallet, only set for outgoing trxs of
+    /// multisig wallets
+    pub input_signers: Vec<InputSigners>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InputSigners {
+    pub input_index: usize,
+    pub cosigners: Vec<Cosigner>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TrxInput {
+    previous_output_trx: OutPoint,
+    /// Value of the previous output (sats), only known for outputs of the wallet trxs
+    value: Option<u64>,
+    address: Option<Address>,
+    is_mine: bool,
+}
+
+/// Type of an output script, outputs of the non address types have no address
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    /// Witness program of a version without a standard script type
+    UnknownWitness { version: u8 },
+    /// Hex encoded data pushed after the OP_RETURN
+    OpReturn { data: String },
+    BareMultisig { threshold: usize, keys: usize },
+    NonStandard,
+}
+
+impl OutputScriptType {
+    pub fn of(script: &Script) -> Self {
+        if script.is_p2pkh() {
+            OutputScriptType::P2pkh
+        } else if script.is_p2sh() {
+            OutputScriptType::P2sh
+        } else if script.is_v0_p2wpkh() {
+            OutputScriptType::P2wpkh
+        } else if script.is_v0_p2wsh() {
+            OutputScriptType::P2wsh
+        } else if script.is_v1_p2tr() {
+            OutputScriptType::P2tr
+        } else if let Some(version) = script
+            .witness_version()
+            .filter(|_| script.is_witness_program())
+        {
+            OutputScriptType::UnknownWitness {
+                version: version.to_num(),
+            }
+        } else if script.is_op_return() {
+            let mut data = Vec::new();
+            for instruction in script.instructions().skip(1).flatten() {
+                if let Instruction::PushBytes(bytes) = instruction {
+                    data.extend_from_slice(bytes);
+                }
+            }
+            OutputScriptType::OpReturn {
+                data: data.to_hex(),
+            }
+        } else {
+            Self::bare_multisig(script).unwrap_or(OutputScriptType::NonStandard)
         }
-        let mut combined = self.deserialize_psbt(&psbts[0])?;
-        for psbt in &psbts[1..] {
-            combined.combine(self.deserialize_psbt(psbt)?)?;
+    }
+
+    /// Matches the <threshold> <keys...> <number of keys> OP_CHECKMULTISIG script
+    fn bare_multisig(script: &Script) -> Option<Self> {
+        let instructions = script
+            .instructions()
+            .collect::<Result<Vec<Instruction>, _>>()
+            .ok()?;
+        let push_num = |instruction: &Instruction| match instruction {
+            Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+                Class::PushNum(n) if n > 0 => Some(n as usize),
+                _ => None,
+            },
+            _ => None,
+        };
+        match instructions.as_slice() {
+            [first, keys @ .., last, Instruction::Op(OP_CHECKMULTISIG)] => {
+                let threshold = push_num(first)?;
+                let n = push_num(last)?;
+                let all_keys = keys.iter().all(|key| {
+                    matches!(key, Instruction::PushBytes(bytes) if bytes.len() == 33 || bytes.len() == 65)
+                });
+                if n == keys.len() && threshold <= n && all_keys {
+                    Some(OutputScriptType::BareMultisig { threshold, keys: n })
+                } else {
+                    None
+                }
+            }
+            _ => None,
         }
-        self.sync()?;
-        let finalized = self
-            .wallet
-            .finalize_psbt(&mut combined, SignOptions::default())?;
-        if !finalized {
-            return Err(Error::new("provided psbts do not finalize tx"));
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TrxOutput {
+    value: u64,
+    script_pubkey: Script,
+    /// Not set for outputs without an address, e.g. OP_RETURN or bare multisig outputs
+    address: Option<Address>,
+    script_type: OutputScriptType,
+    /// BIP-329 label of the output, or of its address if the output has none
+    label: Option<String>,
+    is_mine: bool,
+    /// Keychain of the wallet the output belongs to
+    keychain: Option<Keychain>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BalanceRequest {
+    #[serde(flatten)]
+    pub descriptors: Descriptors,
+    /// Block height at which the balance is computed, the current balance is returned when
+    /// neither the height nor the time is set
+    pub at_height: Option<u32>,
+    /// Unix timestamp at which the balance is computed
+    pub at_time: Option<u64>,
+}
+
+/// Point of the chain at which a historical balance is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceSnapshot {
+    Height(u32),
+    Time(u64),
+}
+
+impl BalanceSnapshot {
+    pub fn new(at_height: Option<u32>, at_time: Option<u64>) -> Result<Option<Self>, Error> {
+        match (at_height, at_time) {
+            (Some(_), Some(_)) => Err(Error::new("only one of at_height and at_time can be set")),
+            (Some(height), None) => Ok(Some(BalanceSnapshot::Height(height))),
+            (None, Some(time)) => Ok(Some(BalanceSnapshot::Time(time))),
+            (None, None) => Ok(None),
         }
-        Ok(self.serialize_psbt(&combined))
     }
 
-    pub fn verify_proof_of_reserves(&self, message: &str, psbt: &str) -> Result<u64, Error> {
-        self.sync()?;
+    /// Whether the block was mined at or before the snapshot
+    pub fn includes(&self, time: &BlockTime) -> bool {
+        match self {
+            BalanceSnapshot::Height(height) => time.height <= *height,
+            BalanceSnapshot::Time(timestamp) => time.timestamp <= *timestamp,
+        }
+    }
+
+    /// Returns the outputs of the wallet that were confirmed and unspent at the snapshot, with
+    /// the height of the block confirming them, replaying the trxs confirmed up to the snapshot
+    pub fn utxos<F>(
+        &self,
+        trxs: &[TransactionDetails],
+        is_mine: F,
+    ) -> Result<BTreeMap<OutPoint, (TxOut, u32)>, Error>
+    where
+        F: Fn(&Script) -> Result<bool, Error>,
+    {
+        let mut utxos = BTreeMap::new();
+        let mut spent: Vec<OutPoint> = Vec::new();
+        for trx in trxs {
+            let height = match &trx.confirmation_time {
+                Some(time) if self.includes(time) => time.height,
+                _ => continue,
+            };
+            let rtrx = trx
+                .transaction
+                .as_ref()
+                .ok_or_else(|| Error::new(&format!("raw trx not found: {}", trx.txid)))?;
+            spent.extend(rtrx.input.iter().map(|input| input.previous_output));
+            for (vout, output) in rtrx.output.iter().enumerate() {
+                if is_mine(&output.script_pubkey)? {
+                    utxos.insert(
+                        OutPoint::new(trx.txid, vout as u32),
+                        (output.clone(), height),
+                    );
+                }
+            }
+        }
+        for outpoint in spent {
+            utxos.remove(&outpoint);
+        }
+        Ok(utxos)
+    }
+
+    /// Returns the value of the outputs of the wallet that were confirmed and unspent at the
+    /// snapshot
+    pub fn balance<F>(&self, trxs: &[TransactionDetails], is_mine: F) -> Result<u64, Error>
+    where
+        F: Fn(&Script) -> Result<bool, Error>,
+    {
         Ok(self
-            .wallet
-            .verify_proof(&self.deserialize_psbt(psbt)?, message, None)?)
+            .utxos(trxs, is_mine)?
+            .values()
+            .map(|(output, _)| output.value)
+            .sum())
     }
+}
 
-    fn get_external_descriptor(&self) -> Result<Descriptor<DescriptorPublicKey>, Error> {
-        let descriptor = self
-            .wallet
-            .public_descriptor(KeychainKind::External)?
-            .ok_or(bdk::Error::Generic("No descriptor for wallet".to_string()))?;
-        Ok(descriptor)
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProofOfReservesRequest {
+    pub descriptors: Descriptors,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProofOfReserves {
+    pub descriptors: Descriptors,
+    pub psbt: String,
+    pub message: String,
+    /// Verifies the proof against the outputs that were unspent at the block height
+    pub max_block_height: Option<u32>,
+    /// Minimum number of confirmations of the proof outputs at the verified block height
+    pub min_confirmations: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProofOfReservesVerification {
+    /// Amount proven by the proof (sats)
+    pub amount: u64,
+    pub message: String,
+    /// Txid of the challenge input, committing to the message
+    pub challenge_txid: Txid,
+    /// Block height the proof was verified at, not set when verified against the current
+    /// outputs of the wallet, including the unconfirmed ones
+    pub block_height: Option<u32>,
+    /// Outputs spent by the proof, excluding the challenge input
+    pub inputs: Vec<ProvenInput>,
+    /// Proof inputs spent since the verified block height
+    pub spent_inputs: Vec<OutPoint>,
+    /// Amount of the proof inputs that are still unspent (sats), not set when the spent status
+    /// of the outputs is unknown
+    pub unspent_amount: Option<u64>,
+    pub utxo_source: ProofUtxoSource,
+    /// What the verification relies on, given the source of the outputs
+    pub trust_assumption: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProvenInput {
+    pub outpoint: OutPoint,
+    pub value: u64,
+    /// Whether the output is still unspent, not set when unknown
+    pub unspent: Option<bool>,
+}
+
+/// Source of the outputs spent by a proof, which determines what its verification trusts
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProofUtxoSource {
+    /// Outputs of the wallet synced from the Electrum server of the service
+    Wallet,
+    /// Outputs supplied by the caller
+    Snapshot,
+    /// Outputs described by the utxo data inside the proof psbt
+    Psbt,
+}
+
+impl ProofUtxoSource {
+    pub fn trust_assumption(&self) -> &'static str {
+        match self {
+            ProofUtxoSource::Wallet => {
+                "the outputs and their spent status are taken from the Electrum server of the service"
+            }
+            ProofUtxoSource::Snapshot => {
+                "the outputs are taken from the snapshot supplied by the caller, which is trusted to \
+                 list existing unspent outputs"
+            }
+            ProofUtxoSource::Psbt => {
+                "the outputs are taken from the utxo data inside the proof psbt, which is trusted to \
+                 describe existing outputs, their spent status is not checked"
+            }
+        }
     }
+}
 
-    fn serialize_psbt(&self, psbt: &PartiallySignedTransaction) -> String {
-        base64::encode(consensus::serialize(psbt))
+impl ProofOfReservesVerification {
+    /// Verifies the proof against the outputs and returns its report. Every proof verification,
+    /// against a wallet, several wallets or offline, goes through here so that they report the
+    /// same way
+    fn verify(
+        psbt: &PartiallySignedTransaction,
+        message: &str,
+        network: Network,
+        block_height: Option<u32>,
+        utxos: ProofUtxos,
+    ) -> Result<Self, Error> {
+        let (outpoints, unspent, utxo_source) = match utxos {
+            ProofUtxos::Wallet { outpoints, unspent } => {
+                (outpoints, Some(unspent), ProofUtxoSource::Wallet)
+            }
+            ProofUtxos::Snapshot(unspent) => (
+                unspent.clone().into_iter().collect(),
+                Some(unspent),
+                ProofUtxoSource::Snapshot,
+            ),
+            ProofUtxos::Psbt => (psbt_outpoints(psbt)?, None, ProofUtxoSource::Psbt),
+        };
+        let unspent = unspent.as_ref();
+        let proven: BTreeMap<OutPoint, TxOut> = outpoints.iter().cloned().collect();
+        let amount = verify_proof(psbt, message, outpoints, network)?;
+        let mut inputs = Vec::new();
+        let mut spent_inputs = Vec::new();
+        let mut unspent_amount = unspent.map(|_| 0);
+        for outpoint in proof_inputs(psbt) {
+            let value = proven.get(&outpoint).map_or(0, |output| output.value);
+            let is_unspent = unspent.map(|unspent| unspent.contains_key(&outpoint));
+            match is_unspent {
+                Some(true) => unspent_amount = unspent_amount.map(|amount| amount + value),
+                Some(false) => spent_inputs.push(outpoint),
+                None => {}
+            }
+            inputs.push(ProvenInput {
+                outpoint,
+                value,
+                unspent: is_unspent,
+            });
+        }
+        Ok(ProofOfReservesVerification {
+            amount,
+            message: message.to_string(),
+            challenge_txid: challenge_input(psbt).map_or_else(Txid::all_zeros, |input| input.txid),
+            block_height,
+            inputs,
+            spent_inputs,
+            unspent_amount,
+            utxo_source,
+            trust_assumption: utxo_source.trust_assumption().to_string(),
+        })
     }
+}
 
-    fn deserialize_psbt(&self, psbt: &str) -> Result<PartiallySignedTransaction, Error> {
-        let decoded = base64::decode(psbt).unwrap_or(psbt.as_bytes().to_vec());
-        let deserialized = consensus::deserialize(&decoded)?;
-        Ok(deserialized)
+/// Signature status of a proof of reserves
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProofSignersStatus {
+    pub threshold: u32,
+    /// Whether every input of the proof has enough signatures to be finalized
+    pub complete: bool,
+    /// Cosigners that signed any input of the proof
+    pub signed: Vec<Cosigner>,
+    /// Cosigners that have not signed some input of the proof without enough signatures yet,
+    /// the inputs that are complete or finalized need no more signatures
+    pub pending: Vec<Cosigner>,
+    /// Signers of the inputs spending the outputs of the wallet, excluding the challenge input
+    pub inputs: Vec<ProofInputSigners>,
+}
+
+impl ProofSignersStatus {
+    fn new(multisig: &Multisig, inputs: Vec<ProofInputSigners>) -> Self {
+        let signed = multisig
+            .cosigners
+            .iter()
+            .filter(|cosigner| inputs.iter().any(|input| input.signed.contains(cosigner)))
+            .cloned()
+            .collect();
+        let pending = multisig
+            .cosigners
+            .iter()
+            .filter(|cosigner| {
+                inputs
+                    .iter()
+                    .any(|input| !input.complete && input.pending.contains(cosigner))
+            })
+            .cloned()
+            .collect();
+        ProofSignersStatus {
+            threshold: multisig.threshold,
+            complete: inputs.iter().all(|input| input.complete),
+            signed,
+            pending,
+            inputs,
+        }
     }
+}
 
-    fn sync(&self) -> Result<(), Error> {
-        self.wallet
-            .sync(self.blockchain.get_blockchain(), SyncOptions::default())?;
-        Ok(())
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProofInputSigners {
+    pub input_index: usize,
+    pub outpoint: OutPoint,
+    /// Whether the input was already finalized by one of the psbts
+    pub finalized: bool,
+    /// Whether the input has at least the threshold signatures
+    pub complete: bool,
+    pub signed: Vec<Cosigner>,
+    pub pending: Vec<Cosigner>,
+}
+
+impl ProofInputSigners {
+    fn new(
+        input_index: usize,
+        outpoint: OutPoint,
+        finalized: bool,
+        signed: Vec<Cosigner>,
+        multisig: &Multisig,
+    ) -> Self {
+        let pending = multisig
+            .cosigners
+            .iter()
+            .filter(|cosigner| !signed.contains(cosigner))
+            .cloned()
+            .collect();
+        ProofInputSigners {
+            input_index,
+            outpoint,
+            finalized,
+            complete: finalized || signed.len() >= multisig.threshold as usize,
+            signed,
+            pending,
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
+/// Returns the output spent by the input, as described by the psbt
+fn input_utxo(psbt: &PartiallySignedTransaction, input_index: usize) -> Option<TxOut> {
+    let input = psbt.inputs.get(input_index)?;
+    match (&input.witness_utxo, &input.non_witness_utxo) {
+        (Some(utxo), _) => Some(utxo.clone()),
+        (None, Some(trx)) => {
+            let vout = psbt
+                .unsigned_tx
+                .input
+                .get(input_index)?
+                .previous_output
+                .vout;
+            trx.output.get(vout as usize).cloned()
+        }
+        (None, None) => None,
+    }
+}
 
-    use crate::hbdk::*;
-    use bitcoin::util::address::AddressType;
+/// Returns the keychain and derivation index of the input keys, the last two steps of their
+/// bip32 derivation paths
+fn bip32_derivation_path(input: &psbt::Input) -> Option<(KeychainKind, u32)> {
+    input
+        .bip32_derivation
+        .values()
+        .find_map(|(_, path)| match path.as_ref() {
+            [.., ChildNumber::Normal { index: 0 }, ChildNumber::Normal { index }] => {
+                Some((KeychainKind::External, *index))
+            }
+            [.., ChildNumber::Normal { index: 1 }, ChildNumber::Normal { index }] => {
+                Some((KeychainKind::Internal, *index))
+            }
+            _ => None,
+        })
+}
 
-    #[test]
-    fn test_cosigner_descriptor() {
-        let cosigner = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+/// Outputs a proof is verified against
+type ProofOutpoints = Vec<(OutPoint, TxOut)>;
+
+/// Outputs a proof is verified against, along with the outputs known to be unspent, depending on
+/// their source
+pub(crate) enum ProofUtxos {
+    /// Outputs of the synced wallet at the block height and its currently unspent outputs
+    Wallet {
+        outpoints: ProofOutpoints,
+        unspent: BTreeMap<OutPoint, TxOut>,
+    },
+    /// Unspent outputs supplied by the caller
+    Snapshot(BTreeMap<OutPoint, TxOut>),
+    /// Outputs described by the utxo data of the proof psbt, their spent status is unknown
+    Psbt,
+}
+
+fn challenge_input(psbt: &PartiallySignedTransaction) -> Option<OutPoint> {
+    psbt.unsigned_tx
+        .input
+        .first()
+        .map(|input| input.previous_output)
+}
+
+/// Returns the outpoints spent by the proof, the first input is the challenge
+fn proof_inputs(psbt: &PartiallySignedTransaction) -> impl Iterator<Item = OutPoint> + '_ {
+    psbt.unsigned_tx
+        .input
+        .iter()
+        .skip(1)
+        .map(|input| input.previous_output)
+}
+
+/// Returns the outputs spent by the proof inputs as described by the psbt, preferring the
+/// previous trx of the input, committed to by its txid, over the witness utxo
+fn psbt_outpoints(psbt: &PartiallySignedTransaction) -> Result<Vec<(OutPoint, TxOut)>, Error> {
+    let mut outpoints = Vec::new();
+    // The first input is the challenge
+    for (i, (txin, input)) in psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .zip(psbt.inputs.iter())
+        .enumerate()
+        .skip(1)
+    {
+        let outpoint = txin.previous_output;
+        let output = match (&input.non_witness_utxo, &input.witness_utxo) {
+            (Some(trx), _) if trx.txid() == outpoint.txid => trx
+                .output
+                .get(outpoint.vout as usize)
+                .cloned()
+                .ok_or_else(|| {
+                    Error::new(&format!("previous trx output not found for input: {}", i))
+                })?,
+            (Some(_), _) => {
+                return Err(Error::new(&format!(
+                    "previous trx does not match the outpoint of input: {}",
+                    i
+                )))
+            }
+            (None, Some(output)) => output.clone(),
+            (None, None) => {
+                return Err(Error::new(&format!("utxo data not found for input: {}", i)))
+            }
         };
+        outpoints.push((outpoint, output));
+    }
+    Ok(outpoints)
+}
 
-        assert_eq!(cosigner.descriptor(false).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+/// Number of consecutive unused addresses after which the sync stops looking for used addresses
+/// of a keychain, when not configured
+pub const DEFAULT_STOP_GAP: usize = 20;
+/// Largest stop gap a request can set, when not configured
+pub const DEFAULT_MAX_STOP_GAP: usize = 1000;
 
-        let cosigner = Cosigner{
-      xfp:Some("20F24288".to_string()),
-      derivation_path: Some("m/48'/0'/0'/2".to_string()),
-      xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
-    };
+/// Sync status of the wallet along with the chain tip of the backend
+#[derive(Deserialize, Serialize, Debug)]
+pub struct WalletStatus {
+    /// Block height and time of the sync run by this request, the wallet is synced from an empty
+    /// database on every request
+    pub last_sync: Option<BlockTime>,
+    /// Duration of the sync of the wallet (ms)
+    pub sync_duration_ms: u64,
+    pub tip: ChainTip,
+    pub external: KeychainStatus,
+    pub internal: KeychainStatus,
+    pub stop_gap: usize,
+    /// Number of unspent outputs of the wallet
+    pub utxo_count: usize,
+}
 
-        assert_eq!(cosigner.descriptor(true).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*");
+/// Chain tip as reported by the backend, a timestamp far behind the current time means the
+/// backend is lagging
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ChainTip {
+    pub height: u32,
+    pub hash: BlockHash,
+    pub timestamp: u32,
+}
 
-        let cosigner = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("/48'/0'/0'/2".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
-        };
+/// Addresses of a keychain checked by the last sync. The sync checks every address cached by the
+/// wallet, and caches more while the last `stop_gap` of them have trxs
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct KeychainStatus {
+    /// Number of addresses checked by the sync, from index 0
+    pub scanned_addresses: u32,
+    /// Highest derivation index of an address with trxs
+    pub last_used_index: Option<u32>,
+    /// Number of scanned addresses after the last used one
+    pub unused_addresses: u32,
+    /// Stop gap needed to find the used addresses from the ones before them
+    pub required_stop_gap: usize,
+    /// Whether the required stop gap is above the stop gap in use. The used addresses were then
+    /// only found because the wallet checks addresses in batches beyond the stop gap, and a sync
+    /// may miss the next ones
+    pub stop_gap_exceeded: bool,
+}
 
-        assert_eq!(cosigner.descriptor(false).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+impl KeychainStatus {
+    fn new(used_indexes: &BTreeSet<u32>, scanned_addresses: u32, stop_gap: usize) -> Self {
+        let last_used_index = used_indexes.iter().next_back().cloned();
+        let required_stop_gap = required_stop_gap(used_indexes.iter());
+        KeychainStatus {
+            scanned_addresses,
+            last_used_index,
+            unused_addresses: match last_used_index {
+                Some(index) => scanned_addresses.saturating_sub(index + 1),
+                None => scanned_addresses,
+            },
+            required_stop_gap,
+            stop_gap_exceeded: required_stop_gap > stop_gap,
+        }
+    }
+}
 
-        let cosigner = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("48'/0'/0'/2".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
-        };
+/// Maximum number of derivation indexes of a rescan, which derives and checks the addresses from
+/// index 0 up to its end index
+pub const MAX_RESCAN_INDEXES: u32 = 10_000;
 
-        assert_eq!(cosigner.descriptor(false).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RescanRequest {
+    #[serde(flatten)]
+    pub descriptors: Descriptors,
+    /// Start of the range of the returned used addresses, the addresses before it are scanned too
+    pub start_index: u32,
+    /// End of the range of derivation indexes, exclusive
+    pub end_index: u32,
+}
 
-        let cosigner = Cosigner{
-          xfp:None,
-          derivation_path: Some("m/48'/0'/0'/2".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
-        };
+/// Checks the range of a rescan, the addresses are derived and checked from index 0 whatever the
+/// start index, so the end index itself is bounded
+fn verify_rescan_range(start_index: u32, end_index: u32) -> Result<(), Error> {
+    if start_index >= end_index {
+        return Err(Error::new("start_index must be lower than end_index"));
+    }
+    if end_index > MAX_RESCAN_INDEXES {
+        return Err(Error::new(&format!(
+            "rescan end_index is limited to {}",
+            MAX_RESCAN_INDEXES
+        )));
+    }
+    Ok(())
+}
 
-        assert_eq!(cosigner.descriptor(false).unwrap(), "xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+/// Used addresses found by a rescan of both keychains
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RescanResult {
+    /// Balance of the wallet after the rescan (sats)
+    pub balance: u64,
+    pub external: Vec<UsedAddress>,
+    pub internal: Vec<UsedAddress>,
+    /// Smallest stop gap with which a regular sync finds all the used addresses
+    pub required_stop_gap: usize,
+}
 
-        let cosigner = Cosigner{
-      xfp:Some("20F24288".to_string()),
-      derivation_path: None,
-      xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
-    };
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct UsedAddress {
+    pub index: u32,
+    pub address: String,
+    pub trx_count: usize,
+}
 
-        assert_eq!(cosigner.descriptor(false).unwrap(), "xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+/// Returns the smallest stop gap with which a sync finds all the used indexes, one more than the
+/// longest run of unused indexes before a used one
+fn required_stop_gap<'a>(used_indexes: impl Iterator<Item = &'a u32>) -> usize {
+    let mut next = 0;
+    let mut longest_gap = 0;
+    for index in used_indexes {
+        longest_gap = std::cmp::max(longest_gap, index.saturating_sub(next));
+        next = index + 1;
+    }
+    longest_gap as usize + 1
+}
 
-        let cosigner = Cosigner{
-      xfp:None,
+pub struct Blockchain {
+    blockchain: ElectrumBlockchain,
+    network: Network,
+    stop_gap: usize,
+}
+
+impl Blockchain {
+    /// Connects to the Electrum server, the sync of the wallets stops looking for used addresses
+    /// after `stop_gap` consecutive unused ones
+    pub fn new(url: &str, network: Network, stop_gap: usize) -> Result<Self, Error> {
+        let blockchain = ElectrumBlockchain::from_config(&ElectrumBlockchainConfig {
+            url: url.to_string(),
+            socks5: None,
+            retry: 1,
+            timeout: None,
+            stop_gap,
+        })?;
+        Ok(Blockchain {
+            blockchain,
+            network,
+            stop_gap,
+        })
+    }
+
+    pub fn get_blockchain(&self) -> &ElectrumBlockchain {
+        &self.blockchain
+    }
+
+    pub fn broadcast(&self, tx: &Transaction) -> Result<(), Error> {
+        self.blockchain.broadcast(tx)?;
+        Ok(())
+    }
+}
+
+pub struct Wallet<'a> {
+    blockchain: &'a Blockchain,
+    wallet: bdk::Wallet<MemoryDatabase>,
+    labels: labels::Labels,
+}
+
+impl<'a> Wallet<'a> {
+    pub fn from_multisig(blockchain: &'a Blockchain, multisig: &Multisig) -> Result<Self, Error> {
+        multisig.verify_network(blockchain.network)?;
+        Self::from_descriptors(
+            blockchain,
+            &Descriptors::new(multisig.descriptor(false)?, multisig.descriptor(true)?),
+        )
+    }
+
+    pub fn from_descriptors(
+        blockchain: &'a Blockchain,
+        descriptors: &Descriptors,
+    ) -> Result<Self, Error> {
+        let network = blockchain.network;
+        descriptors.verify_network(network)?;
+        Ok(Wallet {
+            blockchain,
+            labels: labels::Labels::default(),
+            wallet: bdk::Wallet::new(
+                &descriptors.descriptor,
+                descriptors.change_descriptor.as_ref(),
+                network,
+                MemoryDatabase::default(),
+            )?,
+        })
+    }
+
+    /// Sets the labels included in the trx and address listings
+    pub fn with_labels(mut self, labels: labels::Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Returns the checksum of the external descriptor, which identifies the wallet
+    pub fn descriptor_checksum(&self) -> Result<String, Error> {
+        descriptor_checksum(&self.get_external_descriptor()?)
+    }
+
+    pub fn get_descriptors(&self) -> Result<Descriptors, Error> {
+        let descriptor = self.get_external_descriptor()?;
+        let change_descriptor = self.wallet.public_descriptor(KeychainKind::Internal)?;
+        let change_descriptor = change_descriptor.map(|desc| desc.to_string());
+        Ok(Descriptors {
+            descriptor: descriptor.to_string(),
+            change_descriptor,
+        })
+    }
+
+    pub fn get_multisig(&self) -> Result<Multisig, Error> {
+        Multisig::from_descriptor(&self.get_external_descriptor()?)
+    }
+
+    pub fn get_new_address(&self) -> Result<AddressInfo, Error> {
+        self.sync()?;
+        let address = self.wallet.get_address(AddressIndex::LastUnused)?;
+        Ok(address)
+    }
+
+    /// Returns count addresses of the keychain starting at the start index, along with the public
+    /// keys of each cosigner derived at the index
+    pub fn get_addresses(
+        &self,
+        keychain: Keychain,
+        start: u32,
+        count: u32,
+        sync: bool,
+    ) -> Result<Vec<DerivedAddress>, Error> {
+        let mut addresses = Self::derive_addresses(&self.wallet, keychain, start, count)?;
+        let used_scripts = if sync {
+            self.sync()?;
+            let mut scripts = HashSet::new();
+            for trx in self.wallet.list_transactions(true)? {
+                if let Some(transaction) = trx.transaction {
+                    scripts.extend(transaction.output.into_iter().map(|o| o.script_pubkey));
+                }
+            }
+            Some(scripts)
+        } else {
+            None
+        };
+        for address in &mut addresses {
+            address.label = self
+                .labels
+                .get_label(labels::LabelType::Addr, &address.address.to_string());
+            address.used = used_scripts
+                .as_ref()
+                .map(|scripts| scripts.contains(&address.address.script_pubkey()));
+        }
+        Ok(addresses)
+    }
+
+    /// Derives count addresses of the keychain starting at the start index, without their label
+    /// and used flag
+    fn derive_addresses(
+        wallet: &bdk::Wallet<MemoryDatabase>,
+        keychain: Keychain,
+        start: u32,
+        count: u32,
+    ) -> Result<Vec<DerivedAddress>, Error> {
+        if count > MAX_ADDRESS_COUNT {
+            return Err(Error::new(&format!(
+                "address count: {} is greater than the maximum: {}",
+                count, MAX_ADDRESS_COUNT
+            )));
+        }
+        let end = start
+            .checked_add(count)
+            .filter(|end| *end <= HARDENED_INDEX)
+            .ok_or_else(|| {
+                Error::new(&format!(
+                    "address range start: {} count: {} exceeds the maximum non hardened index",
+                    start, count
+                ))
+            })?;
+        // bdk falls back to the external descriptor for the change keychain
+        if keychain == Keychain::Change
+            && wallet.public_descriptor(KeychainKind::Internal)?.is_none()
+        {
+            return Err(Error::new("the wallet has no change descriptor"));
+        }
+        let descriptor = wallet.get_descriptor_for_keychain(keychain.into());
+        let mut addresses = Vec::new();
+        for index in start..end {
+            let address = match keychain {
+                Keychain::Receive => wallet.get_address(AddressIndex::Peek(index))?,
+                Keychain::Change => wallet.get_internal_address(AddressIndex::Peek(index))?,
+            };
+            let mut keys = Vec::new();
+            descriptor.at_derivation_index(index).for_each_key(|pk| {
+                keys.push(pk.clone());
+                true
+            });
+            let mut public_keys = Vec::new();
+            for key in keys {
+                public_keys.push(DerivedPublicKey {
+                    xfp: key.master_fingerprint().to_string(),
+                    derivation_path: key.full_derivation_path().to_string(),
+                    public_key: key.derive_public_key(wallet.secp_ctx())?,
+                });
+            }
+            addresses.push(DerivedAddress {
+                label: None,
+                index,
+                keychain,
+                used: None,
+                address: address.address,
+                public_keys,
+            });
+        }
+        Ok(addresses)
+    }
+
+    /// Searches the address in the first search_window indexes of the receive and change keychains
+    pub fn verify_address(
+        &self,
+        address: &str,
+        search_window: u32,
+    ) -> Result<AddressVerification, Error> {
+        if search_window > MAX_ADDRESS_COUNT {
+            return Err(Error::new(&format!(
+                "search window: {} is greater than the maximum: {}",
+                search_window, MAX_ADDRESS_COUNT
+            )));
+        }
+        let address = Address::from_str(address)?;
+        if !address.is_valid_for_network(self.blockchain.network) {
+            return Err(Error::new(&format!(
+                "network mismatch, address: {} is for network: {}, expected: {}",
+                address, address.network, self.blockchain.network
+            )));
+        }
+        let multisig = self.get_multisig()?;
+        let secp = Secp256k1::new();
+        let script_pubkey = address.script_pubkey();
+        let has_change_descriptor = self
+            .wallet
+            .public_descriptor(KeychainKind::Internal)?
+            .is_some();
+        for keychain in [Keychain::Receive, Keychain::Change] {
+            if keychain == Keychain::Change && !has_change_descriptor {
+                continue;
+            }
+            let descriptor = self.wallet.get_descriptor_for_keychain(keychain.into());
+            for index in 0..search_window {
+                if descriptor.at_derivation_index(index).script_pubkey() != script_pubkey {
+                    continue;
+                }
+                let change = keychain == Keychain::Change;
+                let mut public_keys = Vec::new();
+                for cosigner in &multisig.cosigners {
+                    public_keys.push(CosignerPublicKey {
+                        cosigner: cosigner.clone(),
+                        public_key: cosigner.derive_public_key(change, index, &secp)?,
+                    });
+                }
+                return Ok(AddressVerification {
+                    label: self
+                        .labels
+                        .get_label(labels::LabelType::Addr, &address.to_string()),
+                    address,
+                    found: true,
+                    keychain: Some(keychain),
+                    index: Some(index),
+                    script_pubkey: Some(script_pubkey),
+                    public_keys,
+                });
+            }
+        }
+        Ok(AddressVerification {
+            address,
+            found: false,
+            keychain: None,
+            index: None,
+            script_pubkey: None,
+            public_keys: Vec::new(),
+            label: None,
+        })
+    }
+
+    /// Returns the trxs of the wallet matching the filter, ordered by confirmation height and
+    /// txid, the page starts after the trx of the filter cursor
+    pub fn list_trxs(&self, filter: &TrxFilter) -> Result<Vec<TrxDetails>, Error> {
+        self.sync()?;
+        let tip_height = self.blockchain.get_blockchain().get_height()?;
+        filter
+            .apply(self.wallet.list_transactions(true)?)?
+            .into_iter()
+            .map(|otrx| self.to_trx_details(otrx, tip_height))
+            .collect()
+    }
+
+    /// Returns the trx history of the wallet as CSV lines, in ascending order of confirmation
+    /// height and txid, with unconfirmed trxs last. The lines are generated as they are consumed,
+    /// so they can be streamed
+    pub fn export_trxs(
+        &self,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+    ) -> Result<impl Iterator<Item = String>, Error> {
+        let trxs = self.list_trxs(&TrxFilter {
+            order: SortOrder::Asc,
+            ..Default::default()
+        })?;
+        Ok(trxs_to_csv(trxs, start_time, end_time))
+    }
+
+    /// Returns the details of the wallet trx, including the cosigners that signed its inputs
+    pub fn get_trx(&self, txid: &Txid) -> Result<TrxWithSigners, Error> {
+        self.sync()?;
+        let otrx = self
+            .wallet
+            .get_tx(txid, true)?
+            .ok_or_else(|| Error::new(&format!("trx not found: {}", txid)))?;
+        let rtrx = otrx
+            .transaction
+            .clone()
+            .ok_or_else(|| Error::new(&format!("raw trx not found: {}", txid)))?;
+        let block_hash = match &otrx.confirmation_time {
+            Some(time) => Some(
+                self.blockchain
+                    .get_blockchain()
+                    .get_block_hash(time.height as u64)?,
+            ),
+            None => None,
+        };
+        let input_signers = match (TrxDirection::of(&otrx), self.get_multisig()) {
+            (TrxDirection::Incoming, _) | (_, Err(_)) => Vec::new(),
+            (_, Ok(multisig)) => self.get_trx_input_signers(&rtrx, &multisig)?,
+        };
+        let tip_height = self.blockchain.get_blockchain().get_height()?;
+        Ok(TrxWithSigners {
+            details: self.to_trx_details(otrx, tip_height)?,
+            hex: consensus::encode::serialize_hex(&rtrx),
+            block_hash,
+            input_signers,
+        })
+    }
+
+    fn to_trx_details(
+        &self,
+        otrx: TransactionDetails,
+        tip_height: u32,
+    ) -> Result<TrxDetails, Error> {
+        let mut inputs: Vec<TrxInput> = vec![];
+        let mut outputs: Vec<TrxOutput> = vec![];
+        let mut vsize = None;
+        let mut rbf_signalled = None;
+        if let Some(rtrx) = otrx.transaction.as_ref() {
+            vsize = Some(rtrx.vsize());
+            rbf_signalled = Some(rtrx.is_explicitly_rbf());
+            for input in &rtrx.input {
+                let previous_output = self
+                    .wallet
+                    .database()
+                    .get_raw_tx(&input.previous_output.txid)?
+                    .and_then(|ptrx| {
+                        ptrx.output
+                            .get(input.previous_output.vout as usize)
+                            .cloned()
+                    });
+                let (value, address, is_mine) = match previous_output {
+                    Some(previous_output) => (
+                        Some(previous_output.value),
+                        Address::from_script(
+                            &previous_output.script_pubkey,
+                            self.blockchain.network,
+                        )
+                        .ok(),
+                        self.wallet.is_mine(&previous_output.script_pubkey)?,
+                    ),
+                    None => (None, None, false),
+                };
+                inputs.push(TrxInput {
+                    previous_output_trx: input.previous_output,
+                    value,
+                    address,
+                    is_mine,
+                });
+            }
+            for (vout, output) in rtrx.output.iter().enumerate() {
+                let address =
+                    Address::from_script(&output.script_pubkey, self.blockchain.network).ok();
+                let keychain = self
+                    .wallet
+                    .database()
+                    .get_path_from_script_pubkey(&output.script_pubkey)?
+                    .map(|(keychain, _)| keychain.into());
+                outputs.push(TrxOutput {
+                    label: self.labels.output_label(
+                        &OutPoint::new(otrx.txid, vout as u32),
+                        address.as_ref().map(|address| address.to_string()).as_deref(),
+                    ),
+                    address,
+                    script_type: OutputScriptType::of(&output.script_pubkey),
+                    script_pubkey: output.script_pubkey.clone(),
+                    value: output.value,
+                    is_mine: keychain.is_some(),
+                    keychain,
+                });
+            }
+        }
+        Ok(TrxDetails {
+            label: self.labels.tx_label(&otrx.txid),
+            trx_id: otrx.txid,
+            received: otrx.received,
+            sent: otrx.sent,
+            fee: otrx.fee,
+            net: otrx.received as i64 - otrx.sent as i64,
+            direction: TrxDirection::of(&otrx),
+            confirmations: otrx
+                .confirmation_time
+                .as_ref()
+                .map_or(0, |time| tip_height.saturating_sub(time.height) + 1),
+            feerate: otrx
+                .fee
+                .zip(vsize)
+                .map(|(fee, vsize)| fee as f32 / vsize as f32),
+            vsize,
+            rbf_signalled,
+            confirmation_time: otrx.confirmation_time,
+            inputs,
+            outputs,
+        })
+    }
+
+    pub fn get_balance(&self) -> Result<u64, Error> {
+        self.sync()?;
+        let balance = self.wallet.get_balance()?;
+        println!("Balance: {:?}", balance);
+        Ok(balance.get_total())
+    }
+
+    /// Syncs the wallet and returns its sync status, with the chain tip of the backend
+    pub fn get_status(&self) -> Result<WalletStatus, Error> {
+        let start = Instant::now();
+        self.sync()?;
+        let sync_duration_ms = start.elapsed().as_millis() as u64;
+        let height = self.blockchain.blockchain.get_height()?;
+        let header = self.blockchain.blockchain.block_header(height as usize)?;
+        let database = self.wallet.database();
+        let keychain_status =
+            |keychain| Self::get_keychain_status(&database, keychain, self.blockchain.stop_gap);
+        Ok(WalletStatus {
+            last_sync: self
+                .wallet
+                .database()
+                .get_sync_time()?
+                .map(|sync_time| sync_time.block_time),
+            sync_duration_ms,
+            tip: ChainTip {
+                height,
+                hash: header.block_hash(),
+                timestamp: header.time,
+            },
+            external: keychain_status(KeychainKind::External)?,
+            internal: keychain_status(KeychainKind::Internal)?,
+            stop_gap: self.blockchain.stop_gap,
+            utxo_count: self.wallet.list_unspent()?.len(),
+        })
+    }
+
+    /// Syncs the wallet with the addresses of both keychains derived from index 0 up to the end
+    /// index, beyond the stop gap, and returns the used addresses from the start index
+    pub fn rescan(&self, start_index: u32, end_index: u32) -> Result<RescanResult, Error> {
+        verify_rescan_range(start_index, end_index)?;
+        // The sync checks every cached address from index 0 before applying the stop gap, the
+        // start index only filters the returned addresses
+        self.wallet.ensure_addresses_cached(end_index)?;
+        self.sync()?;
+        let mut txids: HashMap<KeychainKind, BTreeMap<u32, HashSet<Txid>>> = HashMap::new();
+        let database = self.wallet.database();
+        for trx in database.iter_raw_txs()? {
+            for output in &trx.output {
+                if let Some((keychain, index)) =
+                    database.get_path_from_script_pubkey(&output.script_pubkey)?
+                {
+                    txids
+                        .entry(keychain)
+                        .or_default()
+                        .entry(index)
+                        .or_default()
+                        .insert(trx.txid());
+                }
+            }
+        }
+        drop(database);
+        let used_addresses = |keychain| -> Result<Vec<UsedAddress>, Error> {
+            let mut used = Vec::new();
+            let indexes = txids
+                .get(&keychain)
+                .into_iter()
+                .flat_map(|indexes| indexes.range(start_index..end_index));
+            for (index, txids) in indexes {
+                let address = match keychain {
+                    KeychainKind::External => {
+                        self.wallet.get_address(AddressIndex::Peek(*index))?
+                    }
+                    KeychainKind::Internal => self
+                        .wallet
+                        .get_internal_address(AddressIndex::Peek(*index))?,
+                };
+                used.push(UsedAddress {
+                    index: *index,
+                    address: address.to_string(),
+                    trx_count: txids.len(),
+                });
+            }
+            Ok(used)
+        };
+        let result = RescanResult {
+            balance: self.wallet.get_balance()?.get_total(),
+            external: used_addresses(KeychainKind::External)?,
+            internal: used_addresses(KeychainKind::Internal)?,
+            required_stop_gap: txids
+                .values()
+                .map(|indexes| required_stop_gap(indexes.keys()))
+                .max()
+                .unwrap_or(1),
+        };
+        Ok(result)
+    }
+
+    /// Returns the status of the keychain from the synced database
+    fn get_keychain_status(
+        database: &MemoryDatabase,
+        keychain: KeychainKind,
+        stop_gap: usize,
+    ) -> Result<KeychainStatus, Error> {
+        let mut used_indexes = BTreeSet::new();
+        for trx in database.iter_raw_txs()? {
+            for output in &trx.output {
+                if let Some((output_keychain, index)) =
+                    database.get_path_from_script_pubkey(&output.script_pubkey)?
+                {
+                    if output_keychain == keychain {
+                        used_indexes.insert(index);
+                    }
+                }
+            }
+        }
+        let scanned_addresses = database.iter_script_pubkeys(Some(keychain))?.len() as u32;
+        Ok(KeychainStatus::new(
+            &used_indexes,
+            scanned_addresses,
+            stop_gap,
+        ))
+    }
+
+    /// Returns the confirmed balance of the wallet at the snapshot
+    pub fn get_balance_at(&self, snapshot: BalanceSnapshot) -> Result<u64, Error> {
+        self.sync()?;
+        let trxs = self.wallet.list_transactions(true)?;
+        snapshot.balance(&trxs, |script| Ok(self.wallet.is_mine(script)?))
+    }
+
+    pub fn build_tx(
+        &self,
+        trx: &Trx,
+    ) -> Result<(PartiallySignedTransaction, TransactionDetails), Error> {
+        trx.verify_network(self.blockchain.network)?;
+        self.sync()?;
+        let mut builder = self.wallet.build_tx();
+        // let to_wallet = Wallet::from_descriptors(self.blockchain, &Descriptors::from_descriptor(trx.to_pub_key.clone()))?;
+        // to_wallet.sync()?;
+        builder
+            // .add_recipient(to_wallet.get_new_address()?.script_pubkey(), trx.amount)
+            .add_recipient(
+                Address::from_str(&trx.to_address)?.script_pubkey(),
+                trx.amount,
+            )
+            .enable_rbf()
+            // .do_not_spend_change()
+            .fee_rate(FeeRate::from_sat_per_vb(trx.fee_sat_per_vb));
+        Ok(builder.finish()?)
+    }
+
+    pub fn build_tx_encoded(&self, trx: &Trx) -> Result<String, Error> {
+        let (psbt, _) = self.build_tx(trx)?;
+        Ok(self.serialize_psbt(&psbt))
+    }
+
+    pub fn get_signers(
+        &self,
+        serialized_psbt: &str,
+        search_radius: u8,
+    ) -> Result<Vec<Cosigner>, Error> {
+        let mut cosigners = Vec::new();
+        let psbt = self.deserialize_psbt(serialized_psbt)?;
+        let multisig = self.get_multisig()?;
+        // The signers of the finalized inputs are found from the derivation indexes of the wallet
+        if psbt
+            .inputs
+            .iter()
+            .any(|input| input.partial_sigs.is_empty() && input.final_script_witness.is_some())
+        {
+            self.sync()?;
+        }
+        for (i, input) in psbt.inputs.iter().enumerate() {
+            if !input.partial_sigs.is_empty() {
+                cosigners.append(&mut Self::get_signers_from_partial_sigs(input, &multisig)?);
+            } else if input.final_script_witness.is_some() {
+                cosigners.append(&mut self.get_signers_from_script_witness(
+                    &psbt,
+                    i,
+                    &multisig,
+                    search_radius,
+                )?);
+            }
+        }
+        Ok(cosigners)
+    }
+
+    fn get_signers_from_partial_sigs(
+        input: &psbt::Input,
+        multisig: &Multisig,
+    ) -> Result<Vec<Cosigner>, Error> {
+        let mut cosigners = Vec::new();
+        for (key, _) in input.partial_sigs.iter() {
+            let keysource = input.bip32_derivation.get(&key.inner).ok_or_else(|| {
+                Error::new(&format!(
+                    "no bip32 derivation found for public key: {}",
+                    key
+                ))
+            })?;
+            if let Some(cosigner) = multisig.find_by_xfp(&keysource.0.to_string()) {
+                cosigners.push((*cosigner).clone());
+            } else {
+                return Err(Error::new(&format!(
+                    "no cosigner found with finger print: {}",
+                    &keysource.0.to_string()
+                )));
+            }
+        }
+        Ok(cosigners)
+    }
+
+    /// Returns the cosigners that signed the finalized input, their keys are derived at the
+    /// derivation index of the spent output when known, or else searched within the radius of the
+    /// last derivation index of both keychains. The wallet must be synced
+    fn get_signers_from_script_witness(
+        &self,
+        psbt: &PartiallySignedTransaction,
+        input_index: usize,
+        multisig: &Multisig,
+        search_radius: u8,
+    ) -> Result<Vec<Cosigner>, Error> {
+        let interpreter = Self::get_tx_interpreter(psbt, input_index)?;
+        let secp = self.wallet.secp_ctx();
+        if let Some((keychain, index)) = self.get_input_path(psbt, input_index)? {
+            let mut finder = multisig.cosigner_finder(
+                keychain == KeychainKind::Internal,
+                index,
+                index + 1,
+                secp,
+            )?;
+            // The bip32 derivations of the psbt may not match the signing keys, which are then
+            // searched within the radius
+            if let Ok(cosigners) = Self::find_signers(&interpreter, &mut finder) {
+                return Ok(cosigners);
+            }
+        }
+        let search_range = |keychain| -> Result<(u32, u32), Error> {
+            let index = self.get_last_derivation_index(keychain)?.unwrap_or(0);
+            Ok((
+                index.saturating_sub(search_radius as u32),
+                index.saturating_add(search_radius as u32 + 1),
+            ))
+        };
+        let (start_idx, end_idx) = search_range(KeychainKind::External)?;
+        let (change_start_idx, change_end_idx) = search_range(KeychainKind::Internal)?;
+        let mut finder = multisig
+            .cosigner_finder(false, start_idx, end_idx, secp)?
+            .with_range(true, change_start_idx, change_end_idx)?;
+        Self::find_signers(&interpreter, &mut finder)
+    }
+
+    /// Returns the keychain and derivation index of the output spent by the input, looked up by
+    /// its script pubkey in the wallet or else taken from the bip32 derivations of the input
+    fn get_input_path(
+        &self,
+        psbt: &PartiallySignedTransaction,
+        input_index: usize,
+    ) -> Result<Option<(KeychainKind, u32)>, Error> {
+        if let Some(utxo) = input_utxo(psbt, input_index) {
+            if let Some(path) = self
+                .wallet
+                .database()
+                .get_path_from_script_pubkey(&utxo.script_pubkey)?
+            {
+                return Ok(Some(path));
+            }
+        }
+        Ok(psbt.inputs.get(input_index).and_then(bip32_derivation_path))
+    }
+
+    /// Returns the cosigners whose signatures are checked by the interpreter
+    fn find_signers(
+        interpreter: &Interpreter,
+        finder: &mut CosignerFinder,
+    ) -> Result<Vec<Cosigner>, Error> {
+        let mut cosigners = Vec::new();
+        for elem in interpreter.iter_assume_sigs() {
+            if let miniscript::interpreter::SatisfiedConstraint::PublicKey { key_sig } = elem? {
+                let (key, _) = key_sig.as_ecdsa().ok_or_else(|| {
+                    Error::new("expected ecdsa signature, found schnorr signature")
+                })?;
+                let cosigner = finder.find_by_public_key(&key)?.ok_or_else(|| {
+                    Error::new(&format!("no cosigner found for public key: {}", key))
+                })?;
+                cosigners.push((*cosigner).clone());
+            }
+        }
+        Ok(cosigners)
+    }
+
+    /// Returns the cosigners that signed each input of the trx spending an output of the wallet,
+    /// the cosigner keys are derived at the exact derivation index of the spent output
+    fn get_trx_input_signers(
+        &self,
+        trx: &Transaction,
+        multisig: &Multisig,
+    ) -> Result<Vec<InputSigners>, Error> {
+        let mut input_signers = Vec::new();
+        for (input_index, input) in trx.input.iter().enumerate() {
+            let previous_output = self
+                .wallet
+                .database()
+                .get_raw_tx(&input.previous_output.txid)?
+                .and_then(|ptrx| {
+                    ptrx.output
+                        .get(input.previous_output.vout as usize)
+                        .cloned()
+                });
+            let previous_output = match previous_output {
+                Some(previous_output) => previous_output,
+                None => continue,
+            };
+            if let Some(cosigners) = self.get_output_signers(
+                &previous_output,
+                &input.script_sig,
+                &input.witness,
+                multisig,
+            )? {
+                input_signers.push(InputSigners {
+                    input_index,
+                    cosigners,
+                });
+            }
+        }
+        Ok(input_signers)
+    }
+
+    /// Returns the cosigners that signed the input spending the output, derived at the exact
+    /// derivation index of the output, `None` when the output does not belong to the wallet
+    fn get_output_signers(
+        &self,
+        previous_output: &TxOut,
+        script_sig: &Script,
+        witness: &Witness,
+        multisig: &Multisig,
+    ) -> Result<Option<Vec<Cosigner>>, Error> {
+        let (keychain, index) = match self
+            .wallet
+            .database()
+            .get_path_from_script_pubkey(&previous_output.script_pubkey)?
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let interpreter =
+            Self::get_interpreter(&previous_output.script_pubkey, script_sig, witness)?;
+        let mut finder = multisig.cosigner_finder(
+            keychain == KeychainKind::Internal,
+            index,
+            index + 1,
+            self.wallet.secp_ctx(),
+        )?;
+        Ok(Some(Self::find_signers(&interpreter, &mut finder)?))
+    }
+
+    fn get_last_derivation_index(&self, keychain: KeychainKind) -> Result<Option<u32>, bdk::Error> {
+        self.wallet.database().deref().get_last_index(keychain)
+    }
+
+    fn get_tx_interpreter(
+        psbt: &PartiallySignedTransaction,
+        input_index: usize,
+    ) -> Result<Interpreter<'_>, Error> {
+        let (txin, input) = match (
+            psbt.unsigned_tx.input.get(input_index),
+            psbt.inputs.get(input_index),
+        ) {
+            (Some(txin), Some(input)) => (txin, input),
+            _ => {
+                return Err(Error::new(&format!(
+                    "failed to create the tx interpreter, input: {} not found",
+                    input_index
+                )))
+            }
+        };
+        let script_sig = &txin.script_sig;
+        if let Some(witness_utxo) = &input.witness_utxo {
+            if let Some(script_witness) = &input.final_script_witness {
+                Self::get_interpreter(&witness_utxo.script_pubkey, script_sig, script_witness)
+            } else {
+                Err(Error::new(
+                    "failed to create the tx interpreter, final script witness not found",
+                ))
+            }
+        } else {
+            Err(Error::new(
+                "failed to create the tx interpreter, witness utxo not found",
+            ))
+        }
+    }
+
+    fn get_interpreter<'txin>(
+        script_pubkey: &'txin Script,
+        script_sig: &'txin Script,
+        witness: &'txin Witness,
+    ) -> Result<Interpreter<'txin>, Error> {
+        Ok(Interpreter::from_txdata(
+            script_pubkey,
+            script_sig,
+            witness,
+            Sequence(0),
+            LockTime::ZERO,
+        )?)
+    }
+
+    pub fn finalize_trx(&self, psbts: &[String], broadcast: bool) -> Result<String, Error> {
+        let tx = self.finalize_psbt(psbts)?.extract_tx();
+        if broadcast {
+            self.blockchain.broadcast(&tx)?;
+        }
+        Ok(tx.txid().to_string())
+    }
+
+    fn combine_psbts(&self, psbts: &[String]) -> Result<PartiallySignedTransaction, Error> {
+        if psbts.is_empty() {
+            return Err(Error::new(&format!(
+                "failed to finalized psbt, there are less than required psbts, found: {}",
+                psbts.len()
+            )));
+        }
+        let mut combined = self.deserialize_psbt(&psbts[0])?;
+        for psbt in &psbts[1..] {
+            combined.combine(self.deserialize_psbt(psbt)?)?;
+        }
+        Ok(combined)
+    }
+
+    fn finalize_psbt(&self, psbts: &[String]) -> Result<PartiallySignedTransaction, Error> {
+        let mut combined = self.combine_psbts(psbts)?;
+        self.sync()?;
+        let finalized = self
+            .wallet
+            .finalize_psbt(&mut combined, SignOptions::default())?;
+        if !finalized {
+            return Err(Error::new("provided psbts do not finalize tx"));
+        }
+        Ok(combined)
+    }
+
+    pub fn create_proof_of_reserves_encoded(&self, message: &str) -> Result<String, Error> {
+        Ok(self.serialize_psbt(&self.create_proof_of_reserves(message)?))
+    }
+
+    pub fn create_proof_of_reserves(
+        &self,
+        message: &str,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        self.sync()?;
+        Ok(self.wallet.create_proof(message)?)
+    }
+
+    pub fn finalize_proof_of_reserves(&self, psbts: &[String]) -> Result<String, Error> {
+        // The first input is the challenge, which requires no signature
+        Ok(self.serialize_psbt(&self.finalize_proof(psbts, 1)?))
+    }
+
+    /// Combines and finalizes the signed psbts of a proof, the first `skip_inputs` inputs require
+    /// no signature
+    fn finalize_proof(
+        &self,
+        psbts: &[String],
+        skip_inputs: usize,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        let mut combined = self.combine_psbts(psbts)?;
+        self.sync()?;
+        let finalized = self
+            .wallet
+            .finalize_psbt(&mut combined, SignOptions::default())?;
+        if !finalized {
+            let pending: Vec<String> = self
+                .get_proof_signers_status(&combined, skip_inputs)?
+                .inputs
+                .iter()
+                .filter(|input| !input.complete)
+                .map(|input| input.input_index.to_string())
+                .collect();
+            return Err(Error::new(&format!(
+                "provided psbts do not finalize tx, inputs pending signatures: {}",
+                pending.join(", ")
+            )));
+        }
+        Ok(combined)
+    }
+
+    /// Returns the cosigners that signed each input of the proof of reserves combined from the
+    /// psbts, and the ones still pending
+    pub fn get_proof_signers(&self, psbts: &[String]) -> Result<ProofSignersStatus, Error> {
+        let combined = self.combine_psbts(psbts)?;
+        self.sync()?;
+        // The first input is the challenge, which requires no signature
+        self.get_proof_signers_status(&combined, 1)
+    }
+
+    fn get_proof_signers_status(
+        &self,
+        psbt: &PartiallySignedTransaction,
+        skip_inputs: usize,
+    ) -> Result<ProofSignersStatus, Error> {
+        let multisig = self.get_multisig()?;
+        let mut inputs = Vec::new();
+        for (input_index, (txin, input)) in psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .zip(psbt.inputs.iter())
+            .enumerate()
+            .skip(skip_inputs)
+        {
+            let finalized =
+                input.final_script_sig.is_some() || input.final_script_witness.is_some();
+            let signed = if !input.partial_sigs.is_empty() {
+                Self::get_signers_from_partial_sigs(input, &multisig)?
+            } else if finalized {
+                match input_utxo(psbt, input_index) {
+                    Some(previous_output) => self
+                        .get_output_signers(
+                            &previous_output,
+                            input.final_script_sig.as_ref().unwrap_or(&txin.script_sig),
+                            input.final_script_witness.as_ref().unwrap_or(&txin.witness),
+                            &multisig,
+                        )?
+                        .unwrap_or_default(),
+                    None => Vec::new(),
+                }
+            } else {
+                Vec::new()
+            };
+            inputs.push(ProofInputSigners::new(
+                input_index,
+                txin.previous_output,
+                finalized,
+                signed,
+                &multisig,
+            ));
+        }
+        Ok(ProofSignersStatus::new(&multisig, inputs))
+    }
+
+    /// Verifies the proof against the current outputs of the wallet, or against the outputs that
+    /// were confirmed and unspent at the max block height with the min confirmations, when set
+    pub fn verify_proof_of_reserves(
+        &self,
+        message: &str,
+        psbt: &str,
+        max_block_height: Option<u32>,
+        min_confirmations: Option<u32>,
+    ) -> Result<ProofOfReservesVerification, Error> {
+        self.sync()?;
+        let psbt = self.deserialize_psbt(psbt)?;
+        let block_height = self.get_proof_block_height(max_block_height, min_confirmations)?;
+        let outpoints = self.get_proof_outpoints(block_height, min_confirmations)?;
+        ProofOfReservesVerification::verify(
+            &psbt,
+            message,
+            self.blockchain.network,
+            block_height,
+            ProofUtxos::Wallet {
+                outpoints,
+                unspent: self.get_unspent_outputs()?,
+            },
+        )
+    }
+
+    /// Returns the block height the proof outputs must be unspent at, the max block height or
+    /// else the tip of the chain when min confirmations are required, none for the current outputs
+    fn get_proof_block_height(
+        &self,
+        max_block_height: Option<u32>,
+        min_confirmations: Option<u32>,
+    ) -> Result<Option<u32>, Error> {
+        match (max_block_height, min_confirmations) {
+            (Some(block_height), _) => Ok(Some(block_height)),
+            (None, Some(_)) => Ok(Some(self.blockchain.get_blockchain().get_height()?)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Returns the outputs of the synced wallet the proof inputs are verified against, the
+    /// current outputs or the ones unspent at the block height with the min confirmations
+    fn get_proof_outpoints(
+        &self,
+        block_height: Option<u32>,
+        min_confirmations: Option<u32>,
+    ) -> Result<ProofOutpoints, Error> {
+        let block_height = match block_height {
+            Some(block_height) => block_height,
+            None => return Ok(self.get_unspent_outputs()?.into_iter().collect()),
+        };
+        let max_confirmation_height =
+            (block_height + 1).saturating_sub(min_confirmations.unwrap_or(1).max(1));
+        let outpoints = BalanceSnapshot::Height(block_height)
+            .utxos(&self.wallet.list_transactions(true)?, |script| {
+                Ok(self.wallet.is_mine(script)?)
+            })?
+            .into_iter()
+            .filter(|(_, (_, height))| *height <= max_confirmation_height)
+            .map(|(outpoint, (output, _))| (outpoint, output))
+            .collect();
+        Ok(outpoints)
+    }
+
+    fn get_unspent_outputs(&self) -> Result<BTreeMap<OutPoint, TxOut>, Error> {
+        Ok(self
+            .wallet
+            .list_unspent()?
+            .into_iter()
+            .map(|utxo| (utxo.outpoint, utxo.txout))
+            .collect())
+    }
+
+    fn get_external_descriptor(&self) -> Result<Descriptor<DescriptorPublicKey>, Error> {
+        let descriptor = self
+            .wallet
+            .public_descriptor(KeychainKind::External)?
+            .ok_or(bdk::Error::Generic("No descriptor for wallet".to_string()))?;
+        Ok(descriptor)
+    }
+
+    fn serialize_psbt(&self, psbt: &PartiallySignedTransaction) -> String {
+        base64::encode(consensus::serialize(psbt))
+    }
+
+    fn deserialize_psbt(&self, psbt: &str) -> Result<PartiallySignedTransaction, Error> {
+        let decoded = base64::decode(psbt).unwrap_or(psbt.as_bytes().to_vec());
+        let deserialized = consensus::deserialize(&decoded)?;
+        Ok(deserialized)
+    }
+
+    fn sync(&self) -> Result<(), Error> {
+        self.wallet
+            .sync(self.blockchain.get_blockchain(), SyncOptions::default())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::hbdk::*;
+    use bdk::electrum_client::Client;
+    use bitcoin::hashes::hex::FromHex as _;
+    use bitcoin::util::address::AddressType;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn test_cosigner_descriptor() {
+        let cosigner = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        assert_eq!(cosigner.descriptor(false).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+
+        let cosigner = Cosigner{
+      xfp:Some("20F24288".to_string()),
+      derivation_path: Some("m/48'/0'/0'/2".to_string()),
+      xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+    };
+
+        assert_eq!(cosigner.descriptor(true).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*");
+
+        let cosigner = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("/48'/0'/0'/2".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        assert_eq!(cosigner.descriptor(false).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+
+        let cosigner = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("48'/0'/0'/2".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        assert_eq!(cosigner.descriptor(false).unwrap(), "[20F24288/48'/0'/0'/2]xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+
+        let cosigner = Cosigner{
+          xfp:None,
+          derivation_path: Some("m/48'/0'/0'/2".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        assert_eq!(cosigner.descriptor(false).unwrap(), "xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+
+        let cosigner = Cosigner{
+      xfp:Some("20F24288".to_string()),
+      derivation_path: None,
+      xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+    };
+
+        assert_eq!(cosigner.descriptor(false).unwrap(), "xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+
+        let cosigner = Cosigner{
+      xfp:None,
+      derivation_path: None,
+      xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+    };
+
+        assert_eq!(cosigner.descriptor(false).unwrap(), "xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+    }
+
+    #[test]
+    fn test_cosigner_descriptor_should_fail_for_non_multisig_xpub() {
+        let cosigner = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        let result = cosigner.descriptor(false);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("xpub is not a multisig xpub. xpub:zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi"));
+    }
+
+    #[test]
+    fn test_multisig_descriptor() {
+        let cosigner1 = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        let cosigner2 = Cosigner{
+          xfp:Some("E9A0CF4A".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        };
+
+        let cosigner3 = Cosigner{
+          xfp:Some("232377FA".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75QjBtEjDZJyE1f494V7uQqy4seryeCKQVGQQSCjutDt1WmwCyBMcY5AgxSjZwgfzBm6V2SULe5DvDLi1n3wHGtrrbsmjd1FDZ938VqVQuC".to_string(),
+        };
+        let mut multisig = Multisig::new(2);
+        multisig.add_cosigner(cosigner1);
+        multisig.add_cosigner(cosigner2);
+
+        assert_eq!(multisig.descriptor(false).unwrap(), "wsh(sortedmulti(2,[20F24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*,[E9A0CF4A/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/0/*))");
+        multisig.add_cosigner(cosigner3);
+
+        assert_eq!(multisig.descriptor(false).unwrap(), "wsh(sortedmulti(2,[20F24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*,[E9A0CF4A/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/0/*,[232377FA/48'/0'/0'/2']xpub6Er7TKATMEfe6r7SXgStfAKA19Kht3XjFzaHvP9Rn6diH8aZwFTxHHtxqqa61h9vwSTW7VFEY6yd2UVpHA4xYKQzHTNXAPuGnNjsmQqLoQB/0/*))");
+        multisig.threshold = 3;
+        assert_eq!(multisig.descriptor(true).unwrap(), "wsh(sortedmulti(3,[20F24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*,[E9A0CF4A/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/1/*,[232377FA/48'/0'/0'/2']xpub6Er7TKATMEfe6r7SXgStfAKA19Kht3XjFzaHvP9Rn6diH8aZwFTxHHtxqqa61h9vwSTW7VFEY6yd2UVpHA4xYKQzHTNXAPuGnNjsmQqLoQB/1/*))");
+    }
+
+    #[test]
+    fn test_multisig_descriptor_should_fail_for_threshold_greater_than_signers() {
+        let cosigner1 = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        let cosigner2 = Cosigner{
+          xfp:Some("E9A0CF4A".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        };
+        let mut cosigners = Multisig::new(3);
+        cosigners.add_cosigner(cosigner1);
+        cosigners.add_cosigner(cosigner2);
+        let result = cosigners.descriptor(false);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "multisig threshold: 3 is greater than the number of cosigners: 2"
+        );
+    }
+
+    #[test]
+    fn test_wallet_get_descriptors() {
+        let cosigner1 = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        let cosigner2 = Cosigner{
+          xfp:Some("E9A0CF4A".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        };
+        let mut multisig = Multisig::new(2);
+        multisig.add_cosigner(cosigner1);
+        multisig.add_cosigner(cosigner2);
+
+        let blockchain = Blockchain::new(
+            "ssl://electrum.blockstream.info:60002",
+            bitcoin::Network::Bitcoin,
+            DEFAULT_STOP_GAP,
+        )
+        .unwrap();
+        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
+        let descriptors = wallet.get_descriptors().unwrap();
+        assert_eq!(descriptors.descriptor, "wsh(sortedmulti(2,[20f24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*,[e9a0cf4a/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/0/*))#8dwny30k");
+        assert_eq!(descriptors.change_descriptor, Some("wsh(sortedmulti(2,[20f24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*,[e9a0cf4a/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/1/*))#77ah2z6r".to_string()));
+    }
+
+    #[test]
+    fn test_multisig_descriptors() {
+        let mut multisig = Multisig::new(2);
+        multisig.add_cosigner(Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        });
+        multisig.add_cosigner(Cosigner{
+          xfp:Some("E9A0CF4A".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        });
+        let descriptors = multisig.descriptors().unwrap();
+        assert_eq!(descriptors.descriptor, "wsh(sortedmulti(2,[20f24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*,[e9a0cf4a/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/0/*))#8dwny30k");
+        assert_eq!(descriptors.change_descriptor, Some("wsh(sortedmulti(2,[20f24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*,[e9a0cf4a/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/1/*))#77ah2z6r".to_string()));
+        let mut parsed = Multisig::from_descriptors(&descriptors).unwrap();
+        assert_eq!(parsed.threshold, 2);
+        assert_cosigners(&mut parsed.cosigners, &mut multisig.cosigners);
+    }
+
+    #[test]
+    fn test_wallet_get_multisig() {
+        let cosigner1 = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        let cosigner2 = Cosigner{
+          xfp:Some("E9A0CF4A".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        };
+        let mut original_multisig = Multisig::new(2);
+        original_multisig.add_cosigner(cosigner1);
+        original_multisig.add_cosigner(cosigner2);
+
+        let blockchain = Blockchain::new(
+            "ssl://electrum.blockstream.info:60002",
+            bitcoin::Network::Bitcoin,
+            DEFAULT_STOP_GAP,
+        )
+        .unwrap();
+        let wallet = Wallet::from_multisig(&blockchain, &original_multisig).unwrap();
+        assert_multisig(&mut original_multisig, &mut wallet.get_multisig().unwrap());
+
+        let cosigner1 = Cosigner:: from_str("Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi").unwrap();
+        let cosigner2 = Cosigner:: from_str("Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM").unwrap();
+
+        let mut original_multisig = Multisig::new(2);
+        original_multisig.add_cosigner(cosigner1);
+        original_multisig.add_cosigner(cosigner2);
+
+        let wallet = Wallet::from_multisig(&blockchain, &original_multisig).unwrap();
+        assert_multisig(&mut original_multisig, &mut wallet.get_multisig().unwrap());
+    }
+
+    #[test]
+    fn test_wallet_get_new_address() {
+        let cosigner1 = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        let cosigner2 = Cosigner{
+          xfp:Some("E9A0CF4A".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        };
+        let mut multisig = Multisig::new(2);
+        multisig.add_cosigner(cosigner1);
+        multisig.add_cosigner(cosigner2);
+
+        let blockchain = Blockchain::new(
+            "ssl://electrum.blockstream.info:60002",
+            bitcoin::Network::Bitcoin,
+            DEFAULT_STOP_GAP,
+        )
+        .unwrap();
+        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
+        let address = wallet.get_new_address().unwrap();
+        assert_eq!(
+            address.to_string(),
+            "bc1q0gepljl6qsn9cn5d3z3jdvsrvrj4xr0j36g8tdhkkqdsw5jmxz2qdfkns2"
+        );
+        //Calling it again should return same address since it has not been used
+        let address = wallet.get_new_address().unwrap();
+        assert_eq!(
+            address.to_string(),
+            "bc1q0gepljl6qsn9cn5d3z3jdvsrvrj4xr0j36g8tdhkkqdsw5jmxz2qdfkns2"
+        );
+    }
+
+    #[test]
+    fn test_wallet_build_tx_encoded() {
+        let cosigner1 = Cosigner{
+      xfp:None,
+      derivation_path: None,
+      xpub: "Vpub5fCyVFyiBup7VKTCTX1vrMP4h2rjz8mxkmwzS8PZ1hZVf3U1AhKU49AYkom3KXDS4jLNyvnvobWkpkESVT3n8RkpwKWCBcUiV3y7wFFRktE".to_string(),
+    };
+
+        let cosigner2 = Cosigner{
+      xfp:None,
+      derivation_path: None,
+      xpub: "Vpub5fL9LPRhSA6g5b56Dbgo6ruJ1DASCWE12jwiViwygh6NL3ALDoXVQpsiRApMSjvfotEKpSW9J2RgXoJLvDni2mT9mYicKrisXzQaMGBJfYv".to_string(),
+    };
+        let mut multisig = Multisig::new(2);
+        multisig.add_cosigner(cosigner1);
+        multisig.add_cosigner(cosigner2);
+
+        let blockchain = Blockchain::new(
+            "ssl://electrum.blockstream.info:60002",
+            bitcoin::Network::Testnet,
+            DEFAULT_STOP_GAP,
+        )
+        .unwrap();
+        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
+        let trx = Trx {
+            descriptors: wallet.get_descriptors().unwrap(),
+            to_address: "tb1qhfku74zsrhvre7053xqsnh36gsey3ur7slwwnfn04g5506rmdchqrf7w30"
+                .to_string(),
+            amount: 10_000,
+            fee_sat_per_vb: 5.0,
+        };
+        println!("Trx: {:#?}", trx);
+        println!("Address: {:#?}", wallet.get_new_address().unwrap());
+        println!("Balance: {:#?}", wallet.get_balance().unwrap());
+        wallet.build_tx_encoded(&trx).unwrap();
+    }
+
+    #[test]
+    fn test_address() {
+        let address =
+            Address::from_str("tb1qhfku74zsrhvre7053xqsnh36gsey3ur7slwwnfn04g5506rmdchqrf7w30")
+                .unwrap();
+        assert_eq!(
+            address.to_string(),
+            "tb1qhfku74zsrhvre7053xqsnh36gsey3ur7slwwnfn04g5506rmdchqrf7w30"
+        );
+        assert_eq!(address.address_type().unwrap(), AddressType::P2wsh);
+
+        let address = Address::from_str("tb1q0tuqrsd5tqqa9l3juwn9un7lgax7u3mg6uzglf").unwrap();
+        assert_eq!(
+            address.to_string(),
+            "tb1q0tuqrsd5tqqa9l3juwn9un7lgax7u3mg6uzglf"
+        );
+        assert_eq!(address.address_type().unwrap(), AddressType::P2wpkh);
+    }
+
+    #[test]
+    fn test_cosigner_from_str_for_full_xpub() {
+        let cosigner =
+      Cosigner::from_str("[0CDB4EE2/48'/0'/0'/2']Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui/1/*").unwrap();
+
+        assert_eq!(cosigner.xfp, Some(String::from("0cdb4ee2")));
+        assert_eq!(
+            cosigner.derivation_path,
+            Some(String::from("m/48'/0'/0'/2'"))
+        );
+        assert_eq!(
+      cosigner.xpub,
+      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
+    );
+    }
+
+    #[test]
+    fn test_cosigner_from_str_for_xpub() {
+        let cosigner =
+      Cosigner::from_str("Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui").unwrap();
+
+        assert_eq!(cosigner.xfp, None);
+        assert_eq!(cosigner.derivation_path, None);
+        assert_eq!(
+      cosigner.xpub,
+      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
+    );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid xpub format, xpub")]
+    fn test_cosigner_from_str_should_fail_for_invalid_xpub() {
+        Cosigner::from_str("[asd]Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui").unwrap();
+    }
+
+    fn assert_multisig(ms1: &mut Multisig, ms2: &mut Multisig) {
+        assert_eq!(ms1.threshold, ms2.threshold);
+        assert_cosigners(&mut ms1.cosigners, &mut ms2.cosigners);
+    }
+
+    fn assert_cosigners(css1: &mut [Cosigner], css2: &mut [Cosigner]) {
+        assert_eq!(
+            css1.len(),
+            css2.len(),
+            "Different number of cosigners, cs1: {}, cs2: {}",
+            css1.len(),
+            css2.len()
+        );
+        let mut iter1 = css1.iter_mut();
+        for cs1 in css2 {
+            let rcs2 = iter1.find(|cs2| cs1.xpub == cs2.xpub);
+            assert!(rcs2.is_some(), "Cosigner not found: {:?}", cs1);
+            assert_eq!(cs1, rcs2.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cosigner_try_from_cosigner_shadow_for_xpub_with_full_xpub() {
+        let shadow = CosignerShadow {
+      xfp: None,
+      xpub: String::from("[0CDB4EE2/48'/0'/0'/2']Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui/1/*"),
+      derivation_path: None,
+      proof: None
+    };
+        let cosigner = Cosigner::try_from(shadow).unwrap();
+
+        assert_eq!(cosigner.xfp, Some(String::from("0cdb4ee2")));
+        assert_eq!(
+            cosigner.derivation_path,
+            Some(String::from("m/48'/0'/0'/2'"))
+        );
+        assert_eq!(
+      cosigner.xpub,
+      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
+    );
+    }
+
+    #[test]
+    fn test_cosigner_try_from_cosigner_shadow_for_xpub_with_xpub() {
+        let shadow = CosignerShadow {
+      xfp: None,
+      xpub: String::from("Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"),
+      derivation_path: None,
+      proof: None
+    };
+        let cosigner = Cosigner::try_from(shadow).unwrap();
+
+        assert_eq!(cosigner.xfp, None);
+        assert_eq!(cosigner.derivation_path, None);
+        assert_eq!(
+      cosigner.xpub,
+      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
+    );
+    }
+
+    #[test]
+    fn test_cosigner_try_from_cosigner_shadow_for_all_properties_set() {
+        let shadow = CosignerShadow {
+      xfp: Some(String::from("0CDB4EE2")),
+      xpub: String::from("Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"),
+      derivation_path: Some(String::from("m/48'/0'/0'/2'")),
+      proof: None
+    };
+        let cosigner = Cosigner::try_from(shadow).unwrap();
+
+        assert_eq!(cosigner.xfp, Some(String::from("0cdb4ee2")));
+        assert_eq!(
+            cosigner.derivation_path,
+            Some(String::from("m/48'/0'/0'/2'"))
+        );
+        assert_eq!(
+      cosigner.xpub,
+      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
+    );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid xpub format, xpub")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_invalid_xpub() {
+        let shadow = CosignerShadow {
+      xfp: None,
+      xpub: String::from("[asda]Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"),
       derivation_path: None,
-      xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+      proof: None
     };
+        Cosigner::try_from(shadow).unwrap();
+    }
 
-        assert_eq!(cosigner.descriptor(false).unwrap(), "xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*");
+    #[test]
+    fn test_multisig_verify_network() {
+        let multisig = get_test_multisig();
+        assert!(multisig.verify_network(Network::Testnet).is_ok());
+        assert!(multisig.verify_network(Network::Regtest).is_ok());
+        let err = multisig.verify_network(Network::Bitcoin).unwrap_err();
+        assert!(err.to_string().starts_with("network mismatch, xpub: Vpub5knpWjcH"));
     }
 
     #[test]
-    fn test_cosigner_descriptor_should_fail_for_non_multisig_xpub() {
-        let cosigner = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
-        };
+    fn test_descriptors_verify_network() {
+        let multisig = get_test_multisig();
+        let descriptors = Descriptors::new(
+            multisig.descriptor(false).unwrap(),
+            multisig.descriptor(true).unwrap(),
+        );
+        assert!(descriptors.verify_network(Network::Testnet).is_ok());
+        assert!(descriptors
+            .verify_network(Network::Bitcoin)
+            .unwrap_err()
+            .to_string()
+            .starts_with("network mismatch, descriptor key: tpub"));
+
+        let mut descriptors = Descriptors::from_descriptor(multisig.descriptor(false).unwrap());
+        descriptors.change_descriptor = Some(String::from("wsh(sortedmulti(2,[20F24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*,[E9A0CF4A/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/1/*))"));
+        assert!(descriptors
+            .verify_network(Network::Testnet)
+            .unwrap_err()
+            .to_string()
+            .starts_with("network mismatch, descriptor key: xpub6F2hcB5PLR3L"));
+    }
 
-        let result = cosigner.descriptor(false);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("xpub is not a multisig xpub. xpub:zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi"));
+    #[test]
+    fn test_descriptors_checksum() {
+        let descriptor = "wsh(sortedmulti(2,[20f24288/48'/0'/0'/2']tpubD9zJG3Z4c9LLBCTeEcq64yFtVtfDHffWspDxKLY3apTbu4ocjFoD4vXz4XV2tfMAEQ8p9Km6CiEHBYqVhhG3qPPEcBZqPnwYuWx9RVmiVLz/0/*,[e9a0cf4a/48'/0'/0'/2']tpubDA5kZcnunRMnATJYbo9ar5CR5zFCs5SsHmP69noNWEFwyhSPnCDmuwUND3qAvsqyBwUtm2BGurKz5nFvACpHkFzwvmupdsbznAFMNypghFB/0/*))";
+        let descriptors = Descriptors::from_descriptor(descriptor.to_string());
+        assert_eq!(descriptors.checksum().unwrap(), "aakuctju");
+        let descriptors = Descriptors::from_descriptor(format!("{}#aakuctju", descriptor));
+        assert_eq!(descriptors.checksum().unwrap(), "aakuctju");
     }
 
     #[test]
-    fn test_multisig_descriptor() {
-        let cosigner1 = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+    fn test_trx_verify_network() {
+        let multisig = get_test_multisig();
+        let mut trx = Trx {
+            descriptors: Descriptors::new(
+                multisig.descriptor(false).unwrap(),
+                multisig.descriptor(true).unwrap(),
+            ),
+            to_address: Address::p2wsh(&Script::new(), Network::Testnet).to_string(),
+            amount: 10000,
+            fee_sat_per_vb: 1.0,
         };
+        assert!(trx.verify_network(Network::Testnet).is_ok());
+
+        trx.to_address = Address::p2wsh(&Script::new(), Network::Bitcoin).to_string();
+        assert!(trx
+            .verify_network(Network::Testnet)
+            .unwrap_err()
+            .to_string()
+            .starts_with("network mismatch, address: bc1q"));
+    }
 
-        let cosigner2 = Cosigner{
-          xfp:Some("E9A0CF4A".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
-        };
+    fn get_test_trx(n: u8, height: Option<u32>, received: u64, sent: u64) -> TransactionDetails {
+        TransactionDetails {
+            transaction: None,
+            txid: Txid::from_slice(&[n; 32]).unwrap(),
+            received,
+            sent,
+            fee: Some(100),
+            confirmation_time: height.map(|height| BlockTime {
+                height,
+                timestamp: 1_600_000_000 + height as u64 * 600,
+            }),
+        }
+    }
 
-        let cosigner3 = Cosigner{
-          xfp:Some("232377FA".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub75QjBtEjDZJyE1f494V7uQqy4seryeCKQVGQQSCjutDt1WmwCyBMcY5AgxSjZwgfzBm6V2SULe5DvDLi1n3wHGtrrbsmjd1FDZ938VqVQuC".to_string(),
-        };
-        let mut multisig = Multisig::new(2);
-        multisig.add_cosigner(cosigner1);
-        multisig.add_cosigner(cosigner2);
+    fn get_test_trxs() -> Vec<TransactionDetails> {
+        vec![
+            get_test_trx(1, Some(100), 10000, 0),
+            get_test_trx(2, None, 5000, 0),
+            get_test_trx(3, Some(300), 9900, 10000),
+            get_test_trx(4, Some(200), 2000, 10000),
+            get_test_trx(5, Some(200), 3000, 0),
+        ]
+    }
 
-        assert_eq!(multisig.descriptor(false).unwrap(), "wsh(sortedmulti(2,[20F24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*,[E9A0CF4A/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/0/*))");
-        multisig.add_cosigner(cosigner3);
+    fn get_test_trx_details(otrx: TransactionDetails, label: Option<&str>) -> TrxDetails {
+        TrxDetails {
+            trx_id: otrx.txid,
+            received: otrx.received,
+            sent: otrx.sent,
+            fee: otrx.fee,
+            net: otrx.received as i64 - otrx.sent as i64,
+            direction: TrxDirection::of(&otrx),
+            confirmation_time: otrx.confirmation_time,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            label: label.map(|label| label.to_string()),
+            confirmations: 0,
+            vsize: None,
+            feerate: None,
+            rbf_signalled: None,
+        }
+    }
 
-        assert_eq!(multisig.descriptor(false).unwrap(), "wsh(sortedmulti(2,[20F24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*,[E9A0CF4A/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/0/*,[232377FA/48'/0'/0'/2']xpub6Er7TKATMEfe6r7SXgStfAKA19Kht3XjFzaHvP9Rn6diH8aZwFTxHHtxqqa61h9vwSTW7VFEY6yd2UVpHA4xYKQzHTNXAPuGnNjsmQqLoQB/0/*))");
-        multisig.threshold = 3;
-        assert_eq!(multisig.descriptor(true).unwrap(), "wsh(sortedmulti(3,[20F24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*,[E9A0CF4A/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/1/*,[232377FA/48'/0'/0'/2']xpub6Er7TKATMEfe6r7SXgStfAKA19Kht3XjFzaHvP9Rn6diH8aZwFTxHHtxqqa61h9vwSTW7VFEY6yd2UVpHA4xYKQzHTNXAPuGnNjsmQqLoQB/1/*))");
+    fn txids(trxs: &[TransactionDetails]) -> Vec<Txid> {
+        trxs.iter().map(|trx| trx.txid).collect()
     }
 
     #[test]
-    fn test_multisig_descriptor_should_fail_for_threshold_greater_than_signers() {
-        let cosigner1 = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
-        };
+    fn test_trx_direction() {
+        let trxs = get_test_trxs();
+        assert_eq!(TrxDirection::of(&trxs[0]), TrxDirection::Incoming);
+        assert_eq!(TrxDirection::of(&trxs[2]), TrxDirection::SelfTransfer);
+        assert_eq!(TrxDirection::of(&trxs[3]), TrxDirection::Outgoing);
+    }
 
-        let cosigner2 = Cosigner{
-          xfp:Some("E9A0CF4A".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
-        };
-        let mut cosigners = Multisig::new(3);
-        cosigners.add_cosigner(cosigner1);
-        cosigners.add_cosigner(cosigner2);
-        let result = cosigners.descriptor(false);
-        assert!(result.is_err());
+    #[test]
+    fn test_trx_filter_order_and_pagination() {
+        let trxs = get_test_trxs();
+        let mut filter = TrxFilter::default();
         assert_eq!(
-            result.unwrap_err().to_string(),
-            "multisig threshold: 3 is greater than the number of cosigners: 2"
+            txids(&filter.apply(get_test_trxs()).unwrap()),
+            vec![trxs[1].txid, trxs[2].txid, trxs[4].txid, trxs[3].txid, trxs[0].txid]
         );
+
+        filter.order = SortOrder::Asc;
+        filter.limit = Some(2);
+        let page = filter.apply(get_test_trxs()).unwrap();
+        assert_eq!(txids(&page), vec![trxs[0].txid, trxs[3].txid]);
+        filter.cursor = Some(page[1].txid);
+        let page = filter.apply(get_test_trxs()).unwrap();
+        assert_eq!(txids(&page), vec![trxs[4].txid, trxs[2].txid]);
+        filter.cursor = Some(page[1].txid);
+        let page = filter.apply(get_test_trxs()).unwrap();
+        assert_eq!(txids(&page), vec![trxs[1].txid]);
+        filter.cursor = Some(page[0].txid);
+        assert!(filter.apply(get_test_trxs()).unwrap().is_empty());
     }
 
     #[test]
-    fn test_wallet_get_descriptors() {
-        let cosigner1 = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+    #[should_panic(expected = "cursor trx not found")]
+    fn test_trx_filter_should_fail_for_unknown_cursor() {
+        let filter = TrxFilter {
+            cursor: Some(Txid::from_slice(&[9; 32]).unwrap()),
+            ..Default::default()
         };
+        filter.apply(get_test_trxs()).unwrap();
+    }
 
-        let cosigner2 = Cosigner{
-          xfp:Some("E9A0CF4A".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+    #[test]
+    fn test_trx_filter_filters() {
+        let trxs = get_test_trxs();
+        let filter = TrxFilter {
+            order: SortOrder::Asc,
+            start_height: Some(150),
+            end_height: Some(250),
+            ..Default::default()
         };
-        let mut multisig = Multisig::new(2);
-        multisig.add_cosigner(cosigner1);
-        multisig.add_cosigner(cosigner2);
+        assert_eq!(
+            txids(&filter.apply(get_test_trxs()).unwrap()),
+            vec![trxs[3].txid, trxs[4].txid]
+        );
 
-        let blockchain = Blockchain::new(
-            "ssl://electrum.blockstream.info:60002",
-            bitcoin::Network::Bitcoin,
-        )
-        .unwrap();
-        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
-        let descriptors = wallet.get_descriptors().unwrap();
-        assert_eq!(descriptors.descriptor, "wsh(sortedmulti(2,[20f24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/0/*,[e9a0cf4a/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/0/*))#8dwny30k");
-        assert_eq!(descriptors.change_descriptor, Some("wsh(sortedmulti(2,[20f24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH/1/*,[e9a0cf4a/48'/0'/0'/2']xpub6EBypM14fbYFBG4yqLgTyzR69yrH8QE9kKkPHq2sxzat4WQEyFMR18sQT9csK54oGpsPR81hjFfJc3mVXnAZKaTdj51be8Ny2pVUb3jv6MC/1/*))#77ah2z6r".to_string()));
-    }
+        let filter = TrxFilter {
+            start_time: Some(1_600_000_000 + 250 * 600),
+            ..Default::default()
+        };
+        assert_eq!(txids(&filter.apply(get_test_trxs()).unwrap()), vec![trxs[2].txid]);
 
-    #[test]
-    fn test_wallet_get_multisig() {
-        let cosigner1 = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        let filter = TrxFilter {
+            direction: Some(TrxDirection::Incoming),
+            confirmed: Some(true),
+            order: SortOrder::Asc,
+            ..Default::default()
         };
+        assert_eq!(
+            txids(&filter.apply(get_test_trxs()).unwrap()),
+            vec![trxs[0].txid, trxs[4].txid]
+        );
 
-        let cosigner2 = Cosigner{
-          xfp:Some("E9A0CF4A".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        let filter = TrxFilter {
+            confirmed: Some(false),
+            ..Default::default()
         };
-        let mut original_multisig = Multisig::new(2);
-        original_multisig.add_cosigner(cosigner1);
-        original_multisig.add_cosigner(cosigner2);
+        assert_eq!(txids(&filter.apply(get_test_trxs()).unwrap()), vec![trxs[1].txid]);
+    }
 
-        let blockchain = Blockchain::new(
-            "ssl://electrum.blockstream.info:60002",
-            bitcoin::Network::Bitcoin,
+    #[test]
+    fn test_list_trxs_request_deserialize() {
+        let req: ListTrxsRequest = rocket::serde::json::serde_json::from_str(
+            r#"{"descriptor": "wsh(multi(1,xpub))", "limit": 10, "direction": "self-transfer", "order": "asc"}"#,
         )
         .unwrap();
-        let wallet = Wallet::from_multisig(&blockchain, &original_multisig).unwrap();
-        assert_multisig(&mut original_multisig, &mut wallet.get_multisig().unwrap());
-
-        let cosigner1 = Cosigner:: from_str("Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi").unwrap();
-        let cosigner2 = Cosigner:: from_str("Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM").unwrap();
-
-        let mut original_multisig = Multisig::new(2);
-        original_multisig.add_cosigner(cosigner1);
-        original_multisig.add_cosigner(cosigner2);
+        assert_eq!(req.descriptors.descriptor, "wsh(multi(1,xpub))");
+        assert_eq!(req.descriptors.change_descriptor, None);
+        assert_eq!(req.filter.limit, Some(10));
+        assert_eq!(req.filter.direction, Some(TrxDirection::SelfTransfer));
+        assert_eq!(req.filter.order, SortOrder::Asc);
+
+        let req: ListTrxsRequest =
+            rocket::serde::json::serde_json::from_str(r#"{"descriptor": "wsh(multi(1,xpub))"}"#)
+                .unwrap();
+        assert_eq!(req.filter.order, SortOrder::Desc);
+        assert_eq!(req.filter.limit, None);
+    }
 
-        let wallet = Wallet::from_multisig(&blockchain, &original_multisig).unwrap();
-        assert_multisig(&mut original_multisig, &mut wallet.get_multisig().unwrap());
+    fn get_test_raw_trx(
+        height: Option<u32>,
+        previous_output: OutPoint,
+        outputs: &[(u8, u64)],
+    ) -> TransactionDetails {
+        let transaction = Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn {
+                previous_output,
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: outputs
+                .iter()
+                .map(|(script, value)| bitcoin::TxOut {
+                    value: *value,
+                    script_pubkey: Script::from(vec![*script]),
+                })
+                .collect(),
+        };
+        TransactionDetails {
+            txid: transaction.txid(),
+            transaction: Some(transaction),
+            received: 0,
+            sent: 0,
+            fee: None,
+            confirmation_time: height.map(|height| BlockTime {
+                height,
+                timestamp: 1_600_000_000 + height as u64 * 600,
+            }),
+        }
     }
 
     #[test]
-    fn test_wallet_get_new_address() {
-        let cosigner1 = Cosigner{
-          xfp:Some("20F24288".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+    fn test_balance_snapshot() {
+        // OP_1 outputs belong to the wallet, OP_2 outputs don't
+        let external = |n: u8| OutPoint::new(Txid::from_slice(&[n; 32]).unwrap(), 0);
+        let trx1 = get_test_raw_trx(Some(100), external(1), &[(0x51, 10000), (0x52, 500)]);
+        let trx2 = get_test_raw_trx(
+            Some(200),
+            OutPoint::new(trx1.txid, 0),
+            &[(0x52, 6900), (0x51, 3000)],
+        );
+        let trx3 = get_test_raw_trx(Some(300), external(2), &[(0x51, 2000)]);
+        let trx4 = get_test_raw_trx(None, external(3), &[(0x51, 1000)]);
+        let trxs = vec![trx4, trx3, trx2, trx1];
+        let balance = |snapshot: BalanceSnapshot| {
+            snapshot
+                .balance(&trxs, |script| Ok(script.as_bytes() == [0x51]))
+                .unwrap()
         };
+        assert_eq!(balance(BalanceSnapshot::Height(99)), 0);
+        assert_eq!(balance(BalanceSnapshot::Height(100)), 10000);
+        assert_eq!(balance(BalanceSnapshot::Height(250)), 3000);
+        assert_eq!(balance(BalanceSnapshot::Height(300)), 5000);
+        assert_eq!(balance(BalanceSnapshot::Height(u32::MAX)), 5000);
+        assert_eq!(balance(BalanceSnapshot::Time(1_600_000_000 + 200 * 600 - 1)), 10000);
+        assert_eq!(balance(BalanceSnapshot::Time(1_600_000_000 + 200 * 600)), 3000);
+
+        assert_eq!(BalanceSnapshot::new(None, None).unwrap(), None);
+        assert_eq!(
+            BalanceSnapshot::new(Some(100), None).unwrap(),
+            Some(BalanceSnapshot::Height(100))
+        );
+        assert!(BalanceSnapshot::new(Some(100), Some(1_600_000_000)).is_err());
+    }
 
-        let cosigner2 = Cosigner{
-          xfp:Some("E9A0CF4A".to_string()),
-          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
-          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
-        };
-        let mut multisig = Multisig::new(2);
-        multisig.add_cosigner(cosigner1);
-        multisig.add_cosigner(cosigner2);
+    #[test]
+    fn test_balance_snapshot_utxos() {
+        let external = |n: u8| OutPoint::new(Txid::from_slice(&[n; 32]).unwrap(), 0);
+        let trx1 = get_test_raw_trx(Some(100), external(1), &[(0x51, 10000)]);
+        let trx2 = get_test_raw_trx(Some(200), external(2), &[(0x51, 2000), (0x51, 3000)]);
+        let trx3 = get_test_raw_trx(Some(300), OutPoint::new(trx2.txid, 1), &[(0x52, 2900)]);
+        let (txid1, txid2) = (trx1.txid, trx2.txid);
+        let trxs = vec![trx1, trx2, trx3];
+        let utxos = BalanceSnapshot::Height(250)
+            .utxos(&trxs, |script| Ok(script.as_bytes() == [0x51]))
+            .unwrap();
+        let heights: Vec<(OutPoint, u32)> = utxos
+            .iter()
+            .map(|(outpoint, (_, height))| (*outpoint, *height))
+            .collect();
+        let mut expected = vec![
+            (OutPoint::new(txid1, 0), 100),
+            (OutPoint::new(txid2, 0), 200),
+            (OutPoint::new(txid2, 1), 200),
+        ];
+        expected.sort();
+        assert_eq!(heights, expected);
+        let utxos = BalanceSnapshot::Height(300)
+            .utxos(&trxs, |script| Ok(script.as_bytes() == [0x51]))
+            .unwrap();
+        assert!(!utxos.contains_key(&OutPoint::new(txid2, 1)));
+    }
 
-        let blockchain = Blockchain::new(
-            "ssl://electrum.blockstream.info:60002",
-            bitcoin::Network::Bitcoin,
-        )
-        .unwrap();
-        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
-        let address = wallet.get_new_address().unwrap();
+    #[test]
+    fn test_proof_error_reason() {
+        let err = Error::from(bdk_reserves::reserves::ProofError::NotSignedInput(2));
         assert_eq!(
-            address.to_string(),
-            "bc1q0gepljl6qsn9cn5d3z3jdvsrvrj4xr0j36g8tdhkkqdsw5jmxz2qdfkns2"
+            err.reason(),
+            Some(&errors::ProofFailure::MissingSignature { input_index: 2 })
         );
-        //Calling it again should return same address since it has not been used
-        let address = wallet.get_new_address().unwrap();
         assert_eq!(
-            address.to_string(),
-            "bc1q0gepljl6qsn9cn5d3z3jdvsrvrj4xr0j36g8tdhkkqdsw5jmxz2qdfkns2"
+            rocket::serde::json::serde_json::to_string(&err).unwrap(),
+            r#"{"details":"proof of reserves error, input: 2 is not signed","reason":{"type":"MissingSignature","input_index":2}}"#
+        );
+        let err = Error::from(bdk_reserves::reserves::ProofError::ChallengeInputMismatch);
+        assert_eq!(err.reason(), Some(&errors::ProofFailure::WrongMessage));
+        assert_eq!(
+            rocket::serde::json::serde_json::to_string(&Error::new("invalid xpub")).unwrap(),
+            r#"{"details":"invalid xpub"}"#
         );
     }
 
     #[test]
-    fn test_wallet_build_tx_encoded() {
-        let cosigner1 = Cosigner{
-      xfp:None,
-      derivation_path: None,
-      xpub: "Vpub5fCyVFyiBup7VKTCTX1vrMP4h2rjz8mxkmwzS8PZ1hZVf3U1AhKU49AYkom3KXDS4jLNyvnvobWkpkESVT3n8RkpwKWCBcUiV3y7wFFRktE".to_string(),
-    };
+    fn test_trxs_to_csv() {
+        let filter = TrxFilter {
+            order: SortOrder::Asc,
+            ..Default::default()
+        };
+        let trxs: Vec<TrxDetails> = filter
+            .apply(get_test_trxs())
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(i, trx)| get_test_trx_details(trx, (i == 1).then(|| "Rent, March")))
+            .collect();
+        let txids: Vec<Txid> = trxs.iter().map(|trx| trx.trx_id).collect();
+        let csv: String = trxs_to_csv(trxs, None, None).collect();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], TRXS_CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            format!(
+                "2020-09-14T05:06:40Z,{},incoming,10000,,10000,100,",
+                txids[0]
+            )
+        );
+        assert_eq!(
+            lines[2],
+            format!(
+                "2020-09-14T21:46:40Z,{},outgoing,-7900,100,2000,200,\"Rent, March\"",
+                txids[1]
+            )
+        );
+        assert!(lines[4].ends_with(",self-transfer,0,100,4900,300,"));
+        assert_eq!(lines[5], format!(",{},incoming,5000,,9900,,", txids[4]));
+
+        // The running balance includes the trxs before the range
+        let trxs: Vec<TrxDetails> = filter
+            .apply(get_test_trxs())
+            .unwrap()
+            .into_iter()
+            .map(|trx| get_test_trx_details(trx, None))
+            .collect();
+        let csv: String = trxs_to_csv(
+            trxs,
+            Some(1_600_000_000 + 200 * 600),
+            Some(1_600_000_000 + 200 * 600),
+        )
+        .collect();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains(",-7900,100,2000,200,"));
+        assert!(lines[2].contains(",3000,,5000,200,"));
+    }
 
-        let cosigner2 = Cosigner{
-      xfp:None,
-      derivation_path: None,
-      xpub: "Vpub5fL9LPRhSA6g5b56Dbgo6ruJ1DASCWE12jwiViwygh6NL3ALDoXVQpsiRApMSjvfotEKpSW9J2RgXoJLvDni2mT9mYicKrisXzQaMGBJfYv".to_string(),
-    };
-        let mut multisig = Multisig::new(2);
-        multisig.add_cosigner(cosigner1);
-        multisig.add_cosigner(cosigner2);
+    #[test]
+    fn test_output_script_type() {
+        let script_type = |hex: &str| OutputScriptType::of(&Script::from_str(hex).unwrap());
+        assert_eq!(
+            script_type("0020f4850c7e037fa450047b881639563906d8356200d9a23f4c4e04f8b1535c77e8"),
+            OutputScriptType::P2wsh
+        );
+        assert_eq!(
+            script_type("001457a8cd35be516abcc64a81e5dd4e46cca4aa42da"),
+            OutputScriptType::P2wpkh
+        );
+        assert_eq!(
+            script_type("52210292e43b2c8b656c21dd04da745966a7bc77c6df9d3e0c8bfed438eebf9ec554622103bb52dfc3463e7804aad1d0a7fb738d9e9f27eb6d5e130d2a6c322aa17eb9d09b52ae"),
+            OutputScriptType::BareMultisig {
+                threshold: 2,
+                keys: 2
+            }
+        );
+        assert_eq!(
+            script_type("6a0b68656c6c6f20776f726c64"),
+            OutputScriptType::OpReturn {
+                data: "68656c6c6f20776f726c64".to_string()
+            }
+        );
+        assert_eq!(
+            script_type("5220000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"),
+            OutputScriptType::UnknownWitness { version: 2 }
+        );
+        assert_eq!(script_type("51"), OutputScriptType::NonStandard);
+        assert_eq!(
+            rocket::serde::json::serde_json::to_string(&OutputScriptType::OpReturn {
+                data: "00".to_string()
+            })
+            .unwrap(),
+            r#"{"type":"op_return","data":"00"}"#
+        );
+    }
 
-        let blockchain = Blockchain::new(
-            "ssl://electrum.blockstream.info:60002",
-            bitcoin::Network::Testnet,
-        )
-        .unwrap();
-        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
-        let trx = Trx {
-            descriptors: wallet.get_descriptors().unwrap(),
-            to_address: "tb1qhfku74zsrhvre7053xqsnh36gsey3ur7slwwnfn04g5506rmdchqrf7w30"
-                .to_string(),
-            amount: 10_000,
-            fee_sat_per_vb: 5.0,
+    /// Returns a cosigner shadow with a proof for the challenge, signing the proof message of the
+    /// cosigner unless another message is given
+    fn get_test_cosigner_shadow(
+        path: &str,
+        proof_path: Option<&str>,
+        challenge: &str,
+        message: Option<&str>,
+    ) -> CosignerShadow {
+        use bitcoin::secp256k1::Message;
+        use bitcoin::util::bip32::ExtendedPrivKey;
+        use bitcoin::util::misc::{signed_msg_hash, MessageSignature};
+
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Testnet, &[7; 32]).unwrap();
+        let xprv = master
+            .derive_priv(&secp, &DerivationPath::from_str(path).unwrap())
+            .unwrap();
+        let signing_xprv = match proof_path {
+            Some(proof_path) => xprv
+                .derive_priv(
+                    &secp,
+                    &DerivationPath::from_str(&format!("m/{}", proof_path)).unwrap(),
+                )
+                .unwrap(),
+            None => xprv,
         };
-        println!("Trx: {:#?}", trx);
-        println!("Address: {:#?}", wallet.get_new_address().unwrap());
-        println!("Balance: {:#?}", wallet.get_balance().unwrap());
-        wallet.build_tx_encoded(&trx).unwrap();
+        let cosigner = Cosigner {
+            xfp: Some(master.fingerprint(&secp).to_string()),
+            xpub: util::to_segwit_native_multisig_xpub(
+                &ExtendedPubKey::from_priv(&secp, &xprv).to_string(),
+            )
+            .unwrap(),
+            derivation_path: Some(path.to_string()),
+        };
+        let message = match message {
+            Some(message) => message.to_string(),
+            None => cosigner.proof_message(challenge).unwrap(),
+        };
+        let sign = |private_key| {
+            let signature = secp.sign_ecdsa_recoverable(
+                &Message::from_slice(&signed_msg_hash(&message)).unwrap(),
+                private_key,
+            );
+            base64::encode(MessageSignature::new(signature, true).serialize())
+        };
+        CosignerShadow {
+            xfp: cosigner.xfp,
+            xpub: cosigner.xpub,
+            derivation_path: cosigner.derivation_path,
+            proof: Some(CosignerProof {
+                challenge: challenge.to_string(),
+                signature: sign(&signing_xprv.private_key),
+                path: proof_path.map(|proof_path| proof_path.to_string()),
+                xfp_signature: Some(sign(&master.private_key)),
+            }),
+        }
     }
 
     #[test]
-    fn test_address() {
-        let address =
-            Address::from_str("tb1qhfku74zsrhvre7053xqsnh36gsey3ur7slwwnfn04g5506rmdchqrf7w30")
-                .unwrap();
+    fn test_cosigner_try_from_cosigner_shadow_with_proof() {
+        let challenge = CosignerChallenge::new().challenge;
+        let shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        let xfp = shadow.xfp.clone();
+        let cosigner = Cosigner::try_from(shadow).unwrap();
+        assert_eq!(cosigner.xfp, xfp);
+        assert_eq!(cosigner.derivation_path, Some("m/48'/1'/0'/2'".to_string()));
+
+        let challenge = CosignerChallenge::new().challenge;
+        let shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", Some("0/3"), &challenge, None);
+        assert!(Cosigner::try_from(shadow).is_ok());
+    }
+
+    #[test]
+    fn test_cosigner_proof_message() {
+        let cosigner = Cosigner {
+            xfp: Some("20F24288".to_string()),
+            derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+            xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
         assert_eq!(
-            address.to_string(),
-            "tb1qhfku74zsrhvre7053xqsnh36gsey3ur7slwwnfn04g5506rmdchqrf7w30"
+            cosigner.proof_message("challenge").unwrap(),
+            "COSIGNER PROOF 1.0\nchallenge\n[20f24288/48'/0'/0'/2']xpub6F2hcB5PLR3L7BSjEeVCqCho8oC7m23U5jF48mkwEPdn5zmhmayu6tSPacmGUuG4LU1rJT7sRr6QJz8mrVkTCadMCaQHisQFQrP4y1uRvYH"
         );
-        assert_eq!(address.address_type().unwrap(), AddressType::P2wsh);
+    }
+
+    #[test]
+    #[should_panic(expected = "cosigner proof signature was not generated by a key of the xpub")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_invalid_proof_signature() {
+        let challenge = CosignerChallenge::new().challenge;
+        let shadow =
+            get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, Some("Another message"));
+        Cosigner::try_from(shadow).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "cosigner proof signature was not generated by a key of the xpub")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_message_of_another_key_origin() {
+        let challenge = CosignerChallenge::new().challenge;
+        let mut shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        let cosigner = Cosigner {
+            xfp: Some("0cdb4ee2".to_string()),
+            xpub: shadow.xpub.clone(),
+            derivation_path: shadow.derivation_path.clone(),
+        };
+        let message = cosigner.proof_message(&challenge).unwrap();
+        // signed for another xfp, so the signature does not match the message of the cosigner
+        shadow.proof.as_mut().unwrap().signature =
+            get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, Some(&message))
+                .proof
+                .unwrap()
+                .signature;
+        Cosigner::try_from(shadow).unwrap();
+    }
 
-        let address = Address::from_str("tb1q0tuqrsd5tqqa9l3juwn9un7lgax7u3mg6uzglf").unwrap();
-        assert_eq!(
-            address.to_string(),
-            "tb1q0tuqrsd5tqqa9l3juwn9un7lgax7u3mg6uzglf"
-        );
-        assert_eq!(address.address_type().unwrap(), AddressType::P2wpkh);
+    #[test]
+    #[should_panic(expected = "cosigner proof challenge already used for the key")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_replayed_challenge() {
+        let challenge = CosignerChallenge::new().challenge;
+        let shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        assert!(Cosigner::try_from(shadow).is_ok());
+        let shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        Cosigner::try_from(shadow).unwrap();
     }
 
     #[test]
-    fn test_cosigner_from_str_for_full_xpub() {
-        let cosigner =
-      Cosigner::from_str("[0CDB4EE2/48'/0'/0'/2']Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui/1/*").unwrap();
+    #[should_panic(expected = "invalid cosigner proof challenge")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_forged_challenge() {
+        let challenge = CosignerChallenge::new().challenge;
+        let (payload, _) = challenge.rsplit_once('-').unwrap();
+        let challenge = format!("{}-{}", payload, "00".repeat(32));
+        let shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        Cosigner::try_from(shadow).unwrap();
+    }
 
-        assert_eq!(cosigner.xfp, Some(String::from("0cdb4ee2")));
-        assert_eq!(
-            cosigner.derivation_path,
-            Some(String::from("m/48'/0'/0'/2'"))
-        );
-        assert_eq!(
-      cosigner.xpub,
-      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
-    );
+    #[test]
+    #[should_panic(expected = "cosigner proof signature was not generated by a key of the xpub")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_wrong_proof_path() {
+        let challenge = CosignerChallenge::new().challenge;
+        let mut shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", Some("0/3"), &challenge, None);
+        shadow.proof.as_mut().unwrap().path = Some("0/4".to_string());
+        Cosigner::try_from(shadow).unwrap();
     }
 
     #[test]
-    fn test_cosigner_from_str_for_xpub() {
-        let cosigner =
-      Cosigner::from_str("Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui").unwrap();
+    #[should_panic(expected = "does not match the derivation path")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_wrong_depth() {
+        let challenge = CosignerChallenge::new().challenge;
+        let mut shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        shadow.derivation_path = Some("m/48'/1'/2'".to_string());
+        Cosigner::try_from(shadow).unwrap();
+    }
 
-        assert_eq!(cosigner.xfp, None);
-        assert_eq!(cosigner.derivation_path, None);
-        assert_eq!(
-      cosigner.xpub,
-      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
-    );
+    #[test]
+    #[should_panic(expected = "does not match the derivation path")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_wrong_depth_without_proof() {
+        let challenge = CosignerChallenge::new().challenge;
+        let mut shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        shadow.derivation_path = Some("m/48'/1'/2'".to_string());
+        shadow.proof = None;
+        Cosigner::try_from(shadow).unwrap();
     }
 
     #[test]
-    #[should_panic(expected = "invalid xpub format, xpub")]
-    fn test_cosigner_from_str_should_fail_for_invalid_xpub() {
-        Cosigner::from_str("[asd]Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui").unwrap();
+    #[should_panic(expected = "does not match the derivation path")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_wrong_child_number() {
+        let challenge = CosignerChallenge::new().challenge;
+        let mut shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        shadow.derivation_path = Some("m/48'/1'/0'/1'".to_string());
+        shadow.proof = None;
+        Cosigner::try_from(shadow).unwrap();
     }
 
-    fn assert_multisig(ms1: &mut Multisig, ms2: &mut Multisig) {
-        assert_eq!(ms1.threshold, ms2.threshold);
-        assert_cosigners(&mut ms1.cosigners, &mut ms2.cosigners);
+    #[test]
+    #[should_panic(expected = "does not match the xfp")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_wrong_xfp() {
+        let challenge = CosignerChallenge::new().challenge;
+        let mut shadow = get_test_cosigner_shadow("m/48'", None, &challenge, None);
+        shadow.xfp = Some("0cdb4ee2".to_string());
+        Cosigner::try_from(shadow).unwrap();
     }
 
-    fn assert_cosigners(css1: &mut Vec<Cosigner>, css2: &mut Vec<Cosigner>) {
-        assert_eq!(
-            css1.len(),
-            css2.len(),
-            "Different number of cosigners, cs1: {}, cs2: {}",
-            css1.len(),
-            css2.len()
-        );
-        let mut iter1 = css1.iter_mut();
-        for cs1 in css2 {
-            let rcs2 = iter1.find(|cs2| {
-                return cs1.xpub == cs2.xpub;
-            });
-            assert!(rcs2.is_some(), "Cosigner not found: {:?}", cs1);
-            assert_eq!(cs1, rcs2.unwrap());
-        }
+    #[test]
+    #[should_panic(expected = "does not match the xfp")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_wrong_xfp_of_deeper_xpub() {
+        let challenge = CosignerChallenge::new().challenge;
+        let shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        let cosigner = Cosigner {
+            xfp: Some("0cdb4ee2".to_string()),
+            xpub: shadow.xpub.clone(),
+            derivation_path: shadow.derivation_path.clone(),
+        };
+        // The xpub key signs the wrong xfp, only the master key signature can catch it
+        let message = cosigner.proof_message(&challenge).unwrap();
+        let mut shadow =
+            get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, Some(&message));
+        shadow.xfp = cosigner.xfp;
+        Cosigner::try_from(shadow).unwrap();
     }
 
     #[test]
-    fn test_cosigner_try_from_cosigner_shadow_for_xpub_with_full_xpub() {
-        let shadow = CosignerShadow {
-      xfp: None,
-      xpub: String::from("[0CDB4EE2/48'/0'/0'/2']Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui/1/*"),
-      derivation_path: None
-    };
-        let cosigner = Cosigner::try_from(shadow).unwrap();
+    #[should_panic(expected = "cosigner proof xfp signature is required")]
+    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_missing_xfp_signature() {
+        let challenge = CosignerChallenge::new().challenge;
+        let mut shadow = get_test_cosigner_shadow("m/48'/1'/0'/2'", None, &challenge, None);
+        shadow.proof.as_mut().unwrap().xfp_signature = None;
+        Cosigner::try_from(shadow).unwrap();
+    }
 
-        assert_eq!(cosigner.xfp, Some(String::from("0cdb4ee2")));
+    #[test]
+    fn test_wallet_get_addresses() {
+        let cosigner1 = Cosigner{
+          xfp:Some("20F24288".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi".to_string(),
+        };
+
+        let cosigner2 = Cosigner{
+          xfp:Some("E9A0CF4A".to_string()),
+          derivation_path: Some("m/48'/0'/0'/2'".to_string()),
+          xpub: "Zpub74kbYv5LXvBaJRcbSiihEEwuDiBSDztjtpSVmt6C6nB3ntbcEy4pLP3cJGVWsKbYKaAynfCwXnkuVncPGQ9Y4XwWJDWrDMUwTztdxBe7GcM".to_string(),
+        };
+        let mut multisig = Multisig::new(2);
+        multisig.add_cosigner(cosigner1);
+        multisig.add_cosigner(cosigner2);
+        let wallet = bdk::Wallet::new(
+            &multisig.descriptor(false).unwrap(),
+            Some(&multisig.descriptor(true).unwrap()),
+            bitcoin::Network::Bitcoin,
+            MemoryDatabase::default(),
+        )
+        .unwrap();
+        let addresses = Wallet::derive_addresses(&wallet, Keychain::Receive, 0, 3).unwrap();
+        assert_eq!(addresses.len(), 3);
         assert_eq!(
-            cosigner.derivation_path,
-            Some(String::from("m/48'/0'/0'/2'"))
+            addresses[0].address.to_string(),
+            "bc1q0gepljl6qsn9cn5d3z3jdvsrvrj4xr0j36g8tdhkkqdsw5jmxz2qdfkns2"
         );
+        assert_eq!(addresses[2].index, 2);
+        assert_eq!(addresses[2].used, None);
+        assert_eq!(addresses[2].public_keys.len(), 2);
+        assert_eq!(addresses[2].public_keys[0].xfp, "20f24288");
         assert_eq!(
-      cosigner.xpub,
-      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
-    );
-    }
-
-    #[test]
-    fn test_cosigner_try_from_cosigner_shadow_for_xpub_with_xpub() {
-        let shadow = CosignerShadow {
-      xfp: None,
-      xpub: String::from("Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"),
-      derivation_path: None
-    };
-        let cosigner = Cosigner::try_from(shadow).unwrap();
+            addresses[2].public_keys[0].derivation_path,
+            "m/48'/0'/0'/2'/0/2"
+        );
 
-        assert_eq!(cosigner.xfp, None);
-        assert_eq!(cosigner.derivation_path, None);
+        let change_addresses = Wallet::derive_addresses(&wallet, Keychain::Change, 0, 1).unwrap();
+        assert_ne!(change_addresses[0].address, addresses[0].address);
         assert_eq!(
-      cosigner.xpub,
-      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
-    );
+            change_addresses[0].public_keys[1].derivation_path,
+            "m/48'/0'/0'/2'/1/0"
+        );
+
+        assert!(
+            Wallet::derive_addresses(&wallet, Keychain::Receive, 0, MAX_ADDRESS_COUNT + 1).is_err()
+        );
+        assert!(
+            Wallet::derive_addresses(&wallet, Keychain::Receive, HARDENED_INDEX - 1, 2).is_err()
+        );
     }
 
     #[test]
-    fn test_cosigner_try_from_cosigner_shadow_for_all_properties_set() {
-        let shadow = CosignerShadow {
-      xfp: Some(String::from("0CDB4EE2")),
-      xpub: String::from("Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"),
-      derivation_path: Some(String::from("m/48'/0'/0'/2'"))
-    };
-        let cosigner = Cosigner::try_from(shadow).unwrap();
+    #[should_panic(expected = "the wallet has no change descriptor")]
+    fn test_wallet_get_addresses_should_fail_for_change_without_change_descriptor() {
+        let multisig = get_test_multisig();
+        let wallet = bdk::Wallet::new(
+            &multisig.descriptor(false).unwrap(),
+            None,
+            bitcoin::Network::Testnet,
+            MemoryDatabase::default(),
+        )
+        .unwrap();
+        Wallet::derive_addresses(&wallet, Keychain::Change, 0, 1).unwrap();
+    }
 
-        assert_eq!(cosigner.xfp, Some(String::from("0cdb4ee2")));
+    #[test]
+    fn test_wallet_verify_address() {
+        let multisig = get_test_multisig();
+        let blockchain = get_blockchain();
+        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
+        let address = wallet.get_addresses(Keychain::Change, 3, 1, false).unwrap();
+        let address = address[0].address.to_string();
+
+        let verification = wallet.verify_address(&address, 10).unwrap();
+        assert!(verification.found);
+        assert_eq!(verification.keychain, Some(Keychain::Change));
+        assert_eq!(verification.index, Some(3));
+        assert_eq!(verification.public_keys.len(), 4);
+        let secp = Secp256k1::new();
         assert_eq!(
-            cosigner.derivation_path,
-            Some(String::from("m/48'/0'/0'/2'"))
+            verification.public_keys[0].public_key,
+            multisig.cosigners[0].derive_public_key(true, 3, &secp).unwrap()
         );
-        assert_eq!(
-      cosigner.xpub,
-      "Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"      
-    );
+
+        let verification = wallet.verify_address(&address, 3).unwrap();
+        assert!(!verification.found);
+        assert!(verification.public_keys.is_empty());
     }
 
     #[test]
-    #[should_panic(expected = "invalid xpub format, xpub")]
-    fn test_cosigner_try_from_cosigner_shadow_should_fail_for_invalid_xpub() {
-        let shadow = CosignerShadow {
-      xfp: None,
-      xpub: String::from("[asda]Zpub753WkfemgkpJqtboFVaoqHqBSVEQNgEdKmpRuMkNNabVv6ATumRRhNUdrnQopkgLnAxwZxzkh7rDvsCoEvBHuKuojKtSFfuroukMw9Kv1Ui"),
-      derivation_path: None
-    };
-        Cosigner::try_from(shadow).unwrap();
+    fn test_wallet_list_trxs() {
+        let multisig = get_test_multisig();
+        let blockchain = get_blockchain();
+        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
+        let trxs = wallet.list_trxs(&TrxFilter::default()).unwrap();
+        assert!(!trxs.is_empty());
+        for trx in trxs {
+            assert_eq!(trx.net, trx.received as i64 - trx.sent as i64);
+            assert_eq!(trx.confirmation_time.is_some(), trx.confirmations > 0);
+            assert!(trx.vsize.is_some());
+            let received: u64 = trx
+                .outputs
+                .iter()
+                .filter(|output| output.is_mine)
+                .map(|output| output.value)
+                .sum();
+            assert_eq!(received, trx.received);
+            assert!(trx
+                .outputs
+                .iter()
+                .all(|output| output.is_mine == output.keychain.is_some()));
+            let sent: u64 = trx
+                .inputs
+                .iter()
+                .filter(|input| input.is_mine)
+                .map(|input| input.value.unwrap())
+                .sum();
+            assert_eq!(sent, trx.sent);
+        }
+    }
+
+    #[test]
+    fn test_wallet_get_trx() {
+        let multisig = get_test_multisig();
+        let blockchain = get_blockchain();
+        let wallet = Wallet::from_multisig(&blockchain, &multisig).unwrap();
+        for listed in wallet.list_trxs(&TrxFilter::default()).unwrap() {
+            let trx = wallet.get_trx(&listed.trx_id).unwrap();
+            assert_eq!(trx.details.trx_id, listed.trx_id);
+            let rtrx: Transaction =
+                consensus::encode::deserialize(&Vec::<u8>::from_hex(&trx.hex).unwrap()).unwrap();
+            assert_eq!(rtrx.txid(), listed.trx_id);
+            assert_eq!(trx.block_hash.is_some(), trx.details.confirmations > 0);
+            if trx.details.direction == TrxDirection::Incoming {
+                assert!(trx.input_signers.is_empty());
+            } else {
+                assert!(!trx.input_signers.is_empty());
+                for input_signers in trx.input_signers {
+                    assert!(rtrx.input.len() > input_signers.input_index);
+                    assert_eq!(input_signers.cosigners.len() as u32, multisig.threshold);
+                }
+            }
+        }
     }
 
     #[test]
@@ -1207,6 +4164,33 @@ mod tests {
         wallet.get_signers(psbt, search_radius).unwrap();
     }
 
+    #[test]
+    fn test_find_signers_of_finalized_input() {
+        let multisig = get_test_multisig();
+        let secp = Secp256k1::new();
+        let psbt = PartiallySignedTransaction::from_str("cHNidP8BAIkBAAAAAfx15Ttmz6elm9LHqX2jVvqboFTMUrD3OVilRE0RH3HNAQAAAAD9////AhAnAAAAAAAAIgAgapL4iNK+iOvUjmi74v5KOdJq0+brS2MsQt8bZu/jvVy37QAAAAAAACIAIDGu4FBMXgV+irxy6Vz78NrpoH/ezv1eabyuP2wfZkIWAAAAAAABAH0CAAAAAT+oqsgQzz8UeaO8LJJbqVkfwWeYbirVFcC2brDjkKl6AQAAAAD+////AqsLKDAAAAAAFgAUhEkMOtrFy4DnVXpDGJ/qgMsGz5NAGQEAAAAAACIAIJr0KH+bUEFUEaEMV+hkqL0I7NJwuXLireNKjH/A6A7lVNgiAAEBK0AZAQAAAAAAIgAgmvQof5tQQVQRoQxX6GSovQjs0nC5cuKt40qMf8DoDuUiAgNHCPu3PMi2RSVEJl3Hn6FdhYoWxDTEdvVvg0I2V+/JK0cwRAIgQevv55jDdhvw6pJAhjXknVP4JzISWX6RxjqjG3ACYGUCIDGoyjaEXMPEgCDnFti6dhClIHcHXzZpR20XPwDgOzXFASICA2buKGM6tds9euLdfJyg8Ul6rsikU3cyDH2muR3PBPHoRzBEAiB5X6ILUU7AG8PfY9V/Ql5uP/19BHPvDzqVmuueZvDM8gIgcxvLCwoBZQeaAIFzvwEluHbu50F+E2W6dol7uXH5Oo0BAQWLUyECud7M+qn/i4LyHa0YVFUTevc6HBCmEhIqgM5VUADJYuYhA0cI+7c8yLZFJUQmXcefoV2FihbENMR29W+DQjZX78krIQNm7ihjOrXbPXri3XycoPFJeq7IpFN3Mgx9prkdzwTx6CED9Yk2xtBbhmmpBzjCAlfHvo3Qn0Fe2a2fwpLoIaedVk9UriIGArnezPqp/4uC8h2tGFRVE3r3OhwQphISKoDOVVAAyWLmHDuDsJwwAACAAQAAgAAAAIACAACAAAAAAAAAAAAiBgNHCPu3PMi2RSVEJl3Hn6FdhYoWxDTEdvVvg0I2V+/JKxzAuCxoMAAAgAEAAIAAAACAAgAAgAAAAAAAAAAAIgYDZu4oYzq12z164t18nKDxSXquyKRTdzIMfaa5Hc8E8egcXmtqBjAAAIABAACAAAAAgAIAAIAAAAAAAAAAACIGA/WJNsbQW4ZpqQc4wgJXx76N0J9BXtmtn8KS6CGnnVZPHE+CvLcwAACAAQAAgAAAAIACAACAAAAAAAAAAAAAIgICXD7c7JLk/bC0MJD5zpgML2uL4wMMEFXORQ4sBQdVVm8cT4K8tzAAAIABAACAAAAAgAIAAIAAAAAAAQAAACICAxgGi4BP18ux5lOP52MQ9PRPfXgtCjPccC5HIDXRIy2qHF5ragYwAACAAQAAgAAAAIACAACAAAAAAAEAAAAiAgN426KdAJSbNAOC2kc14X1IALYhIcCcdfxOl79acZT8uxw7g7CcMAAAgAEAAIAAAACAAgAAgAAAAAABAAAAIgIDx+fgEf5c9OQxAAZtBX8RDspq6IGsaeMfTHJwPx5cC1AcwLgsaDAAAIABAACAAAAAgAIAAIAAAAAAAQAAAAAiAgJS9efszeiZDbV1CzOv45rPRR1jh1L3pHTzV9blwWUyLxw7g7CcMAAAgAEAAIAAAACAAgAAgAEAAAAAAAAAIgIClDkC1BrgHuuy1WcCHkOQH4KEKYZuo9swFPDX2G+euh0cT4K8tzAAAIABAACAAAAAgAIAAIABAAAAAAAAACICArDjjAJxyeaP5PDwo79XG7P9MN1J9lcgmDMxcCuVMujqHF5ragYwAACAAQAAgAAAAIACAACAAQAAAAAAAAAiAgNrInUs30ukm4ERBVHjsJQfGbYb1dnVy50tk/ym/hyUQBzAuCxoMAAAgAEAAIAAAACAAgAAgAEAAAAAAAAAAA==").unwrap();
+        let input = &psbt.inputs[0];
+        // The signatures are assumed valid, they are attributed to the last keys of the script
+        let sig = input.partial_sigs.values().next().unwrap().to_vec();
+        let witness = Witness::from_vec(vec![
+            Vec::new(),
+            sig.clone(),
+            sig.clone(),
+            sig,
+            input.witness_script.as_ref().unwrap().to_bytes(),
+        ]);
+        let script_pubkey = &input.witness_utxo.as_ref().unwrap().script_pubkey;
+        let script_sig = Script::new();
+        let interpreter = Wallet::get_interpreter(script_pubkey, &script_sig, &witness).unwrap();
+        let mut finder = multisig.cosigner_finder(false, 0, 1, &secp).unwrap();
+        let signers = Wallet::find_signers(&interpreter, &mut finder).unwrap();
+        let xfps: Vec<_> = signers
+            .iter()
+            .map(|signer| signer.xfp.clone().unwrap())
+            .collect();
+        assert_eq!(xfps, vec!["4f82bcb7", "5e6b6a06", "c0b82c68"]);
+    }
+
     #[test]
     fn test_wallet_get_balance_raw() {
         let client = Client::new("ssl://electrum.blockstream.info:60002").unwrap();
@@ -1222,6 +4206,351 @@ mod tests {
         println!("Balance: {:?}", balance);
     }
 
+    #[test]
+    fn test_required_stop_gap() {
+        assert_eq!(required_stop_gap([].iter()), 1);
+        assert_eq!(required_stop_gap([0, 1, 2].iter()), 1);
+        assert_eq!(required_stop_gap([5].iter()), 6);
+        assert_eq!(required_stop_gap([0, 1, 30, 31, 40].iter()), 29);
+    }
+
+    #[test]
+    fn test_verify_rescan_range() {
+        assert!(verify_rescan_range(0, MAX_RESCAN_INDEXES).is_ok());
+        assert!(verify_rescan_range(5, 5).is_err());
+        // The addresses before the start index are scanned too
+        assert!(verify_rescan_range(MAX_RESCAN_INDEXES, MAX_RESCAN_INDEXES + 1).is_err());
+    }
+
+    #[test]
+    fn test_keychain_status() {
+        assert_eq!(
+            KeychainStatus::new(&BTreeSet::new(), 100, 20),
+            KeychainStatus {
+                scanned_addresses: 100,
+                last_used_index: None,
+                unused_addresses: 100,
+                required_stop_gap: 1,
+                stop_gap_exceeded: false,
+            }
+        );
+        let status = KeychainStatus::new(&BTreeSet::from([0, 3, 10]), 100, 20);
+        assert_eq!(status.unused_addresses, 89);
+        assert_eq!(status.required_stop_gap, 7);
+        assert!(!status.stop_gap_exceeded);
+        assert!(!KeychainStatus::new(&BTreeSet::from([19]), 100, 20).stop_gap_exceeded);
+        assert!(KeychainStatus::new(&BTreeSet::from([20]), 100, 20).stop_gap_exceeded);
+    }
+
+    #[test]
+    fn test_wallet_get_keychain_status_of_synced_database() {
+        use bdk::database::BatchOperations;
+
+        let multisig = get_test_multisig();
+        let new_wallet = |database| {
+            bdk::Wallet::new(
+                &multisig.descriptor(false).unwrap(),
+                Some(&multisig.descriptor(true).unwrap()),
+                Network::Testnet,
+                database,
+            )
+            .unwrap()
+        };
+        // Database as left by a sync that found trxs paying to the addresses at the indexes, after
+        // caching the scanned addresses of each keychain
+        let synced_database = |external: &[u32], internal: &[u32], scanned: u32| {
+            let wallet = new_wallet(MemoryDatabase::default());
+            let mut output = Vec::new();
+            for index in external {
+                output.push(wallet.get_address(AddressIndex::Peek(*index)).unwrap());
+            }
+            for index in internal {
+                output.push(
+                    wallet
+                        .get_internal_address(AddressIndex::Peek(*index))
+                        .unwrap(),
+                );
+            }
+            let mut database = MemoryDatabase::default();
+            database
+                .set_raw_tx(&Transaction {
+                    version: 1,
+                    lock_time: bitcoin::PackedLockTime(0),
+                    input: Vec::new(),
+                    output: output
+                        .iter()
+                        .map(|address| TxOut {
+                            value: 1000,
+                            script_pubkey: address.script_pubkey(),
+                        })
+                        .collect(),
+                })
+                .unwrap();
+            database
+                .set_last_index(KeychainKind::External, external[external.len() - 1])
+                .unwrap();
+            let wallet = new_wallet(database);
+            wallet.ensure_addresses_cached(scanned).unwrap();
+            wallet
+        };
+
+        // The sync checks the first 100 cached addresses of each keychain
+        let wallet = synced_database(&[0, 7], &[2], 100);
+        let database = wallet.database();
+        assert_eq!(
+            Wallet::get_keychain_status(&database, KeychainKind::External, 20).unwrap(),
+            KeychainStatus {
+                scanned_addresses: 100,
+                last_used_index: Some(7),
+                unused_addresses: 92,
+                required_stop_gap: 7,
+                stop_gap_exceeded: false,
+            }
+        );
+        let status = Wallet::get_keychain_status(&database, KeychainKind::Internal, 20).unwrap();
+        assert_eq!(status.last_used_index, Some(2));
+        assert_eq!(status.required_stop_gap, 3);
+
+        // The trx at index 90 is within the stop gap of the 100 cached addresses, so the sync
+        // caches a batch of 100 more, and only finds it because of the first batch
+        let wallet = synced_database(&[0, 90], &[], 200);
+        let status =
+            Wallet::get_keychain_status(&wallet.database(), KeychainKind::External, 20).unwrap();
+        assert_eq!(status.scanned_addresses, 200);
+        assert_eq!(status.last_used_index, Some(90));
+        assert_eq!(status.unused_addresses, 109);
+        assert_eq!(status.required_stop_gap, 90);
+        assert!(status.stop_gap_exceeded);
+    }
+
+    #[test]
+    fn test_proof_signers_status() {
+        let multisig = get_test_multisig();
+        let cosigners = &multisig.cosigners;
+        let outpoint = |n: u8| OutPoint::new(Txid::from_slice(&[n; 32]).unwrap(), 0);
+        let inputs = vec![
+            ProofInputSigners::new(1, outpoint(1), false, cosigners[..3].to_vec(), &multisig),
+            ProofInputSigners::new(2, outpoint(2), false, cosigners[..1].to_vec(), &multisig),
+            ProofInputSigners::new(3, outpoint(3), true, Vec::new(), &multisig),
+        ];
+        assert!(inputs[0].complete);
+        assert_eq!(inputs[0].pending, cosigners[3..].to_vec());
+        assert!(!inputs[1].complete);
+        assert_eq!(inputs[1].pending, cosigners[1..].to_vec());
+        assert!(inputs[2].complete);
+        let status = ProofSignersStatus::new(&multisig, inputs);
+        assert_eq!(status.threshold, 3);
+        assert!(!status.complete);
+        assert_eq!(status.signed, cosigners[..3].to_vec());
+        assert_eq!(status.pending, cosigners[1..].to_vec());
+
+        // No cosigner signature is left in the finalized inputs, and none is pending
+        let inputs = vec![
+            ProofInputSigners::new(1, outpoint(1), true, Vec::new(), &multisig),
+            ProofInputSigners::new(2, outpoint(2), true, Vec::new(), &multisig),
+        ];
+        let status = ProofSignersStatus::new(&multisig, inputs);
+        assert!(status.complete);
+        assert!(status.signed.is_empty());
+        assert!(status.pending.is_empty());
+    }
+
+    #[test]
+    fn test_bip32_derivation_path() {
+        let psbt = PartiallySignedTransaction::from_str("cHNidP8BAIkBAAAAAfx15Ttmz6elm9LHqX2jVvqboFTMUrD3OVilRE0RH3HNAQAAAAD9////AhAnAAAAAAAAIgAgapL4iNK+iOvUjmi74v5KOdJq0+brS2MsQt8bZu/jvVy37QAAAAAAACIAIDGu4FBMXgV+irxy6Vz78NrpoH/ezv1eabyuP2wfZkIWAAAAAAABAH0CAAAAAT+oqsgQzz8UeaO8LJJbqVkfwWeYbirVFcC2brDjkKl6AQAAAAD+////AqsLKDAAAAAAFgAUhEkMOtrFy4DnVXpDGJ/qgMsGz5NAGQEAAAAAACIAIJr0KH+bUEFUEaEMV+hkqL0I7NJwuXLireNKjH/A6A7lVNgiAAEBK0AZAQAAAAAAIgAgmvQof5tQQVQRoQxX6GSovQjs0nC5cuKt40qMf8DoDuUiAgNHCPu3PMi2RSVEJl3Hn6FdhYoWxDTEdvVvg0I2V+/JK0cwRAIgQevv55jDdhvw6pJAhjXknVP4JzISWX6RxjqjG3ACYGUCIDGoyjaEXMPEgCDnFti6dhClIHcHXzZpR20XPwDgOzXFAQEFi1MhArnezPqp/4uC8h2tGFRVE3r3OhwQphISKoDOVVAAyWLmIQNHCPu3PMi2RSVEJl3Hn6FdhYoWxDTEdvVvg0I2V+/JKyEDZu4oYzq12z164t18nKDxSXquyKRTdzIMfaa5Hc8E8eghA/WJNsbQW4ZpqQc4wgJXx76N0J9BXtmtn8KS6CGnnVZPVK4iBgK53sz6qf+LgvIdrRhUVRN69zocEKYSEiqAzlVQAMli5hw7g7CcMAAAgAEAAIAAAACAAgAAgAAAAAAAAAAAIgYDRwj7tzzItkUlRCZdx5+hXYWKFsQ0xHb1b4NCNlfvySscwLgsaDAAAIABAACAAAAAgAIAAIAAAAAAAAAAACIGA2buKGM6tds9euLdfJyg8Ul6rsikU3cyDH2muR3PBPHoHF5ragYwAACAAQAAgAAAAIACAACAAAAAAAAAAAAiBgP1iTbG0FuGaakHOMICV8e+jdCfQV7ZrZ/Ckughp51WTxxPgry3MAAAgAEAAIAAAACAAgAAgAAAAAAAAAAAACICAlw+3OyS5P2wtDCQ+c6YDC9ri+MDDBBVzkUOLAUHVVZvHE+CvLcwAACAAQAAgAAAAIACAACAAAAAAAEAAAAiAgMYBouAT9fLseZTj+djEPT0T314LQoz3HAuRyA10SMtqhxea2oGMAAAgAEAAIAAAACAAgAAgAAAAAABAAAAIgIDeNuinQCUmzQDgtpHNeF9SAC2ISHAnHX8Tpe/WnGU/LscO4OwnDAAAIABAACAAAAAgAIAAIAAAAAAAQAAACICA8fn4BH+XPTkMQAGbQV/EQ7KauiBrGnjH0xycD8eXAtQHMC4LGgwAACAAQAAgAAAAIACAACAAAAAAAEAAAAAIgICUvXn7M3omQ21dQszr+Oaz0UdY4dS96R081fW5cFlMi8cO4OwnDAAAIABAACAAAAAgAIAAIABAAAAAAAAACICApQ5AtQa4B7rstVnAh5DkB+ChCmGbqPbMBTw19hvnrodHE+CvLcwAACAAQAAgAAAAIACAACAAQAAAAAAAAAiAgKw44wCccnmj+Tw8KO/Vxuz/TDdSfZXIJgzMXArlTLo6hxea2oGMAAAgAEAAIAAAACAAgAAgAEAAAAAAAAAIgIDayJ1LN9LpJuBEQVR47CUHxm2G9XZ1cudLZP8pv4clEAcwLgsaDAAAIABAACAAAAAgAIAAIABAAAAAAAAAAA=").unwrap();
+        assert_eq!(
+            bip32_derivation_path(&psbt.inputs[0]),
+            Some((KeychainKind::External, 0))
+        );
+        assert_eq!(input_utxo(&psbt, 0).map(|utxo| utxo.value), Some(72000));
+        assert_eq!(bip32_derivation_path(&psbt::Input::default()), None);
+        assert_eq!(input_utxo(&psbt, 1), None);
+    }
+
+    #[test]
+    fn test_cosigner_finder_with_range() {
+        let multisig = get_test_multisig();
+        let secp = Secp256k1::new();
+        let public_key = multisig.cosigners[1]
+            .derive_public_key(false, 3, &secp)
+            .unwrap();
+        let mut finder = multisig.cosigner_finder(true, 0, 5, &secp).unwrap();
+        assert!(finder.find_by_public_key(&public_key).unwrap().is_none());
+        let mut finder = multisig
+            .cosigner_finder(true, 0, 5, &secp)
+            .unwrap()
+            .with_range(false, 2, 4)
+            .unwrap();
+        assert_eq!(
+            finder.find_by_public_key(&public_key).unwrap(),
+            Some(&&multisig.cosigners[1])
+        );
+    }
+
+    #[test]
+    fn test_cosigner_finder_should_fail_for_invalid_range() {
+        let multisig = get_test_multisig();
+        let secp = Secp256k1::new();
+        let err = multisig.cosigner_finder(true, 5, 5, &secp).err().unwrap();
+        assert!(err.to_string().contains("invalid derivation index range"));
+        let err = multisig
+            .cosigner_finder(true, 0, 5, &secp)
+            .unwrap()
+            .with_range(false, 4, 2)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("invalid derivation index range"));
+        // Indexes from 2^31 are hardened and can not be derived from the xpubs
+        let mut finder = multisig
+            .cosigner_finder(false, 0x7FFF_FFFF, 0x8000_0001, &secp)
+            .unwrap();
+        let public_key = multisig.cosigners[0]
+            .derive_public_key(false, 0, &secp)
+            .unwrap();
+        assert!(finder.find_by_public_key(&public_key).is_err());
+    }
+
+    #[test]
+    fn test_get_signers_from_partial_sigs_should_fail_for_missing_bip32_derivation() {
+        let multisig = get_test_multisig();
+        let mut psbt = get_test_signed_psbt();
+        assert_eq!(
+            Wallet::get_signers_from_partial_sigs(&psbt.inputs[0], &multisig)
+                .unwrap()
+                .len(),
+            1
+        );
+        psbt.inputs[0].bip32_derivation.clear();
+        let err = Wallet::get_signers_from_partial_sigs(&psbt.inputs[0], &multisig).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("no bip32 derivation found for public key"));
+    }
+
+    #[test]
+    fn test_get_tx_interpreter_should_fail_for_malformed_input() {
+        let mut psbt = get_test_finalized_psbt();
+        assert!(Wallet::get_tx_interpreter(&psbt, 0).is_ok());
+        assert!(Wallet::get_tx_interpreter(&psbt, 1).is_err());
+        psbt.inputs[0].final_script_witness = Some(Witness::from_vec(vec![vec![0xFF; 3]]));
+        assert!(Wallet::get_tx_interpreter(&psbt, 0).is_err());
+        psbt.inputs[0].final_script_witness = None;
+        assert!(Wallet::get_tx_interpreter(&psbt, 0).is_err());
+        psbt.inputs[0].witness_utxo = None;
+        assert!(Wallet::get_tx_interpreter(&psbt, 0).is_err());
+    }
+
+    #[test]
+    fn test_psbt_analysis_should_not_panic_for_mutated_psbts() {
+        let multisig = get_test_multisig();
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::seed_from_u64(50);
+        for psbt in [get_test_signed_psbt(), get_test_finalized_psbt()] {
+            let serialized = consensus::serialize(&psbt);
+            for _ in 0..2000 {
+                let mut mutated = serialized.clone();
+                for _ in 0..rng.gen_range(1..4) {
+                    let idx = rng.gen_range(0..mutated.len());
+                    mutated[idx] = rng.gen();
+                }
+                if rng.gen_bool(0.1) {
+                    mutated.truncate(rng.gen_range(0..serialized.len()));
+                }
+                if let Ok(mutated) = consensus::deserialize(&mutated) {
+                    analyse_test_psbt(&mutated, &multisig, &secp);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_psbt_analysis_should_not_panic_for_malformed_inputs() {
+        let multisig = get_test_multisig();
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::seed_from_u64(50);
+        let random_bytes = |rng: &mut StdRng| -> Vec<u8> {
+            (0..rng.gen_range(0..80)).map(|_| rng.gen()).collect()
+        };
+        let signed = get_test_signed_psbt();
+        let finalized = get_test_finalized_psbt();
+        for _ in 0..500 {
+            let mut psbt = if rng.gen() {
+                signed.clone()
+            } else {
+                finalized.clone()
+            };
+            let input = &mut psbt.inputs[0];
+            if rng.gen_bool(0.3) {
+                input.bip32_derivation.clear();
+            }
+            if rng.gen_bool(0.3) {
+                input.witness_utxo = None;
+            }
+            if rng.gen_bool(0.3) {
+                input.non_witness_utxo = None;
+            }
+            if let Some(witness) = input.final_script_witness.as_mut() {
+                let mut elements = witness.to_vec();
+                match rng.gen_range(0..4) {
+                    0 => elements.clear(),
+                    1 => elements.push(random_bytes(&mut rng)),
+                    2 => {
+                        let idx = rng.gen_range(0..elements.len());
+                        elements[idx] = random_bytes(&mut rng);
+                    }
+                    _ => {
+                        elements.pop();
+                    }
+                }
+                *witness = Witness::from_vec(elements);
+            }
+            analyse_test_psbt(&psbt, &multisig, &secp);
+        }
+    }
+
+    /// Runs the signer analysis of the wallet on each input of the psbt, only checking that it
+    /// returns instead of panicking
+    fn analyse_test_psbt(
+        psbt: &PartiallySignedTransaction,
+        multisig: &Multisig,
+        secp: &Secp256k1<All>,
+    ) {
+        for (input_index, input) in psbt.inputs.iter().enumerate() {
+            let _ = Wallet::get_signers_from_partial_sigs(input, multisig);
+            let _ = input_utxo(psbt, input_index);
+            let (keychain, index) =
+                bip32_derivation_path(input).unwrap_or((KeychainKind::External, 0));
+            if let Ok(interpreter) = Wallet::get_tx_interpreter(psbt, input_index) {
+                let mut finder = multisig
+                    .cosigner_finder(
+                        keychain == KeychainKind::Internal,
+                        index,
+                        index.saturating_add(1),
+                        secp,
+                    )
+                    .unwrap();
+                let _ = Wallet::find_signers(&interpreter, &mut finder);
+            }
+        }
+    }
+
+    /// Returns the psbt of the multisig signed by one of the cosigners
+    fn get_test_signed_psbt() -> PartiallySignedTransaction {
+        PartiallySignedTransaction::from_str("cHNidP8BAIkBAAAAAfx15Ttmz6elm9LHqX2jVvqboFTMUrD3OVilRE0RH3HNAQAAAAD9////AhAnAAAAAAAAIgAgapL4iNK+iOvUjmi74v5KOdJq0+brS2MsQt8bZu/jvVy37QAAAAAAACIAIDGu4FBMXgV+irxy6Vz78NrpoH/ezv1eabyuP2wfZkIWAAAAAAABAH0CAAAAAT+oqsgQzz8UeaO8LJJbqVkfwWeYbirVFcC2brDjkKl6AQAAAAD+////AqsLKDAAAAAAFgAUhEkMOtrFy4DnVXpDGJ/qgMsGz5NAGQEAAAAAACIAIJr0KH+bUEFUEaEMV+hkqL0I7NJwuXLireNKjH/A6A7lVNgiAAEBK0AZAQAAAAAAIgAgmvQof5tQQVQRoQxX6GSovQjs0nC5cuKt40qMf8DoDuUiAgNHCPu3PMi2RSVEJl3Hn6FdhYoWxDTEdvVvg0I2V+/JK0cwRAIgQevv55jDdhvw6pJAhjXknVP4JzISWX6RxjqjG3ACYGUCIDGoyjaEXMPEgCDnFti6dhClIHcHXzZpR20XPwDgOzXFAQEFi1MhArnezPqp/4uC8h2tGFRVE3r3OhwQphISKoDOVVAAyWLmIQNHCPu3PMi2RSVEJl3Hn6FdhYoWxDTEdvVvg0I2V+/JKyEDZu4oYzq12z164t18nKDxSXquyKRTdzIMfaa5Hc8E8eghA/WJNsbQW4ZpqQc4wgJXx76N0J9BXtmtn8KS6CGnnVZPVK4iBgK53sz6qf+LgvIdrRhUVRN69zocEKYSEiqAzlVQAMli5hw7g7CcMAAAgAEAAIAAAACAAgAAgAAAAAAAAAAAIgYDRwj7tzzItkUlRCZdx5+hXYWKFsQ0xHb1b4NCNlfvySscwLgsaDAAAIABAACAAAAAgAIAAIAAAAAAAAAAACIGA2buKGM6tds9euLdfJyg8Ul6rsikU3cyDH2muR3PBPHoHF5ragYwAACAAQAAgAAAAIACAACAAAAAAAAAAAAiBgP1iTbG0FuGaakHOMICV8e+jdCfQV7ZrZ/Ckughp51WTxxPgry3MAAAgAEAAIAAAACAAgAAgAAAAAAAAAAAACICAlw+3OyS5P2wtDCQ+c6YDC9ri+MDDBBVzkUOLAUHVVZvHE+CvLcwAACAAQAAgAAAAIACAACAAAAAAAEAAAAiAgMYBouAT9fLseZTj+djEPT0T314LQoz3HAuRyA10SMtqhxea2oGMAAAgAEAAIAAAACAAgAAgAAAAAABAAAAIgIDeNuinQCUmzQDgtpHNeF9SAC2ISHAnHX8Tpe/WnGU/LscO4OwnDAAAIABAACAAAAAgAIAAIAAAAAAAQAAACICA8fn4BH+XPTkMQAGbQV/EQ7KauiBrGnjH0xycD8eXAtQHMC4LGgwAACAAQAAgAAAAIACAACAAAAAAAEAAAAAIgICUvXn7M3omQ21dQszr+Oaz0UdY4dS96R081fW5cFlMi8cO4OwnDAAAIABAACAAAAAgAIAAIABAAAAAAAAACICApQ5AtQa4B7rstVnAh5DkB+ChCmGbqPbMBTw19hvnrodHE+CvLcwAACAAQAAgAAAAIACAACAAQAAAAAAAAAiAgKw44wCccnmj+Tw8KO/Vxuz/TDdSfZXIJgzMXArlTLo6hxea2oGMAAAgAEAAIAAAACAAgAAgAEAAAAAAAAAIgIDayJ1LN9LpJuBEQVR47CUHxm2G9XZ1cudLZP8pv4clEAcwLgsaDAAAIABAACAAAAAgAIAAIABAAAAAAAAAAA=").unwrap()
+    }
+
+    /// Returns the signed psbt with the partial sig moved to a final script witness
+    fn get_test_finalized_psbt() -> PartiallySignedTransaction {
+        let mut psbt = get_test_signed_psbt();
+        let input = &mut psbt.inputs[0];
+        let mut elements = vec![Vec::new()];
+        elements.extend(input.partial_sigs.values().map(|sig| sig.to_vec()));
+        elements.push(input.witness_script.as_ref().unwrap().to_bytes());
+        input.final_script_witness = Some(Witness::from_vec(elements));
+        input.partial_sigs.clear();
+        psbt
+    }
+
     fn get_test_multisig() -> Multisig {
         let cosigner1 = Cosigner{
       xfp:Some("c0b82c68".to_string()),
@@ -1259,6 +4588,7 @@ mod tests {
         Blockchain::new(
             "ssl://electrum.blockstream.info:60002",
             bitcoin::Network::Testnet,
+            DEFAULT_STOP_GAP,
         )
         .unwrap()
     }
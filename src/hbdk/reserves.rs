@@ -0,0 +1,508 @@
+//! Proofs of reserves spanning several wallets
+//!
+//! An aggregated proof is a BIP-127 proof with a single challenge input followed by the outputs
+//! of all the wallets, so one signed transaction proves the reserves of several vaults. Each
+//! wallet creates its own proof for the message, and the proofs are merged by appending their
+//! inputs after the challenge input and adding up their outputs.
+
+use crate::hbdk::errors::Error;
+use crate::hbdk::{
+    challenge_input, proof_inputs, Descriptors, ProofOfReservesVerification, ProofUtxos, Wallet,
+};
+use bdk::SignOptions;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{consensus, Network, OutPoint, Script, TxOut};
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AggregatedProofOfReservesRequest {
+    pub descriptors: Vec<Descriptors>,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignedAggregatedProofOfReserves {
+    pub descriptors: Vec<Descriptors>,
+    pub psbts: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AggregatedProofOfReserves {
+    pub descriptors: Vec<Descriptors>,
+    pub psbt: String,
+    pub message: String,
+    pub max_block_height: Option<u32>,
+    pub min_confirmations: Option<u32>,
+}
+
+/// Output the proof inputs can spend, as seen by the caller
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProofUtxo {
+    pub outpoint: OutPoint,
+    pub value: u64,
+    pub script_pubkey: Script,
+}
+
+/// Proof to verify without the wallet, against the supplied utxos or the utxo data of the psbt
+#[derive(Deserialize, Serialize, Debug)]
+pub struct OfflineProofOfReserves {
+    pub psbt: String,
+    pub message: String,
+    /// Unspent outputs the proof is verified against, the utxo data of the psbt is used when not
+    /// set
+    pub utxos: Option<Vec<ProofUtxo>>,
+    /// Block height of the snapshot of the utxos, only reported back
+    pub block_height: Option<u32>,
+}
+
+/// Reserves proven for one of the wallets of an aggregated proof
+#[derive(Deserialize, Serialize, Debug)]
+pub struct WalletReserves {
+    pub descriptor_checksum: String,
+    /// Amount proven by the inputs of the wallet (sats)
+    pub amount: u64,
+    /// Amount of the inputs of the wallet that are still unspent (sats)
+    pub unspent_amount: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AggregatedProofOfReservesVerification {
+    /// Totals across all the wallets
+    #[serde(flatten)]
+    pub total: ProofOfReservesVerification,
+    /// Reserves of each wallet, in the order of the descriptors
+    pub wallets: Vec<WalletReserves>,
+}
+
+/// Creates a proof spending the outputs of all the wallets
+pub fn create_proof(wallets: &[Wallet], message: &str) -> Result<String, Error> {
+    verify_distinct(wallets)?;
+    let mut proofs = Vec::new();
+    for wallet in wallets {
+        proofs.push(wallet.create_proof_of_reserves(message)?);
+    }
+    Ok(wallets[0].serialize_psbt(&merge_proofs(proofs)?))
+}
+
+/// Combines the signed proofs and finalizes the inputs of each wallet, returns the finalized proof
+pub fn finalize_proof(wallets: &[Wallet], psbts: &[String]) -> Result<String, Error> {
+    verify_distinct(wallets)?;
+    if psbts.is_empty() {
+        return Err(Error::new(
+            "failed to finalize the aggregated proof, no psbts provided",
+        ));
+    }
+    let mut combined = wallets[0].deserialize_psbt(&psbts[0])?;
+    for psbt in &psbts[1..] {
+        combined.combine(wallets[0].deserialize_psbt(psbt)?)?;
+    }
+    let mut finalized = false;
+    for wallet in wallets {
+        wallet.sync()?;
+        // Inputs of the other wallets are left untouched, only the last wallet sees them all
+        // finalized
+        finalized = wallet
+            .wallet
+            .finalize_psbt(&mut combined, SignOptions::default())?;
+    }
+    if !finalized {
+        return Err(Error::new(
+            "provided psbts do not finalize the aggregated proof",
+        ));
+    }
+    Ok(wallets[0].serialize_psbt(&combined))
+}
+
+/// Verifies the proof against the outputs of all the wallets, as
+/// [`Wallet::verify_proof_of_reserves`] does for a single wallet, and returns the amounts proven
+/// for each wallet. The tip of the chain is fetched once, so that the outputs of every wallet are
+/// taken at the same block height
+pub fn verify_proof(
+    wallets: &[Wallet],
+    message: &str,
+    psbt: &str,
+    max_block_height: Option<u32>,
+    min_confirmations: Option<u32>,
+) -> Result<AggregatedProofOfReservesVerification, Error> {
+    verify_distinct(wallets)?;
+    let psbt = wallets[0].deserialize_psbt(psbt)?;
+    let mut outpoints = Vec::new();
+    let mut unspent: BTreeMap<OutPoint, TxOut> = BTreeMap::new();
+    let mut wallet_outputs = Vec::new();
+    let block_height = wallets[0].get_proof_block_height(max_block_height, min_confirmations)?;
+    for wallet in wallets {
+        wallet.sync()?;
+        let proof_outpoints = wallet.get_proof_outpoints(block_height, min_confirmations)?;
+        let proven: BTreeMap<OutPoint, TxOut> = proof_outpoints.iter().cloned().collect();
+        let wallet_unspent = wallet.get_unspent_outputs()?;
+        outpoints.extend(proof_outpoints);
+        unspent.extend(wallet_unspent.clone());
+        wallet_outputs.push((proven, wallet_unspent));
+    }
+    let total = ProofOfReservesVerification::verify(
+        &psbt,
+        message,
+        wallets[0].blockchain.network,
+        block_height,
+        ProofUtxos::Wallet { outpoints, unspent },
+    )?;
+    let mut reserves = Vec::new();
+    for (wallet, (wallet_proven, wallet_unspent)) in wallets.iter().zip(wallet_outputs) {
+        let mut wallet_reserves = WalletReserves {
+            descriptor_checksum: wallet.descriptor_checksum()?,
+            amount: 0,
+            unspent_amount: 0,
+        };
+        for outpoint in proof_inputs(&psbt) {
+            if let Some(output) = wallet_proven.get(&outpoint) {
+                wallet_reserves.amount += output.value;
+                if wallet_unspent.contains_key(&outpoint) {
+                    wallet_reserves.unspent_amount += output.value;
+                }
+            }
+        }
+        reserves.push(wallet_reserves);
+    }
+    Ok(AggregatedProofOfReservesVerification {
+        total,
+        wallets: reserves,
+    })
+}
+
+/// Verifies the proof without a wallet or a backend, against the outputs of the snapshot when
+/// provided, or else against the utxo data of the proof psbt
+pub fn verify_proof_offline(
+    proof: &OfflineProofOfReserves,
+    network: Network,
+) -> Result<ProofOfReservesVerification, Error> {
+    let psbt: PartiallySignedTransaction = consensus::deserialize(
+        &base64::decode(&proof.psbt).unwrap_or_else(|_| proof.psbt.as_bytes().to_vec()),
+    )?;
+    let utxos = match &proof.utxos {
+        Some(utxos) => ProofUtxos::Snapshot(
+            utxos
+                .iter()
+                .map(|utxo| {
+                    (
+                        utxo.outpoint,
+                        TxOut {
+                            value: utxo.value,
+                            script_pubkey: utxo.script_pubkey.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        ),
+        None => ProofUtxos::Psbt,
+    };
+    ProofOfReservesVerification::verify(&psbt, &proof.message, network, proof.block_height, utxos)
+}
+
+/// Checks that there is at least one wallet and that no wallet is repeated, so that no reserves
+/// are counted twice
+fn verify_distinct(wallets: &[Wallet]) -> Result<(), Error> {
+    if wallets.is_empty() {
+        return Err(Error::new("at least one descriptor is required"));
+    }
+    let mut checksums = BTreeSet::new();
+    for wallet in wallets {
+        let checksum = wallet.descriptor_checksum()?;
+        if !checksums.insert(checksum.clone()) {
+            return Err(Error::new(&format!("duplicate descriptor: {}", checksum)));
+        }
+    }
+    Ok(())
+}
+
+/// Appends the inputs of the proofs to the first proof, after its challenge input, and adds up
+/// their output values
+fn merge_proofs(
+    proofs: Vec<PartiallySignedTransaction>,
+) -> Result<PartiallySignedTransaction, Error> {
+    let mut proofs = proofs.into_iter();
+    let mut merged = proofs
+        .next()
+        .ok_or_else(|| Error::new("no proofs to merge"))?;
+    let mut outpoints: BTreeSet<OutPoint> = proof_inputs(&merged).collect();
+    for proof in proofs {
+        if challenge_input(&proof) != challenge_input(&merged) {
+            return Err(Error::new("proofs have different challenge inputs"));
+        }
+        for outpoint in proof_inputs(&proof) {
+            if !outpoints.insert(outpoint) {
+                return Err(Error::new(&format!(
+                    "output: {} belongs to more than one wallet",
+                    outpoint
+                )));
+            }
+        }
+        let value: u64 = proof
+            .unsigned_tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum();
+        merged.unsigned_tx.output[0].value += value;
+        merged
+            .unsigned_tx
+            .input
+            .extend(proof.unsigned_tx.input.into_iter().skip(1));
+        merged.inputs.extend(proof.inputs.into_iter().skip(1));
+        merged.xpub.extend(proof.xpub);
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::hbdk::errors::ProofFailure;
+    use crate::hbdk::ProofUtxoSource;
+    use bitcoin::blockdata::opcodes::OP_TRUE;
+    use bitcoin::hashes::{hash160, sha256d, Hash};
+    use bitcoin::util::address::Payload;
+    use bitcoin::{
+        Address, PackedLockTime, PubkeyHash, Sequence, Transaction, TxIn, Txid, Witness,
+    };
+
+    fn get_test_proof(challenge: u8, inputs: &[u8], value: u64) -> PartiallySignedTransaction {
+        let input = |n: u8| TxIn {
+            previous_output: OutPoint::new(Txid::from_slice(&[n; 32]).unwrap(), 0),
+            script_sig: Script::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        PartiallySignedTransaction::from_unsigned_tx(Transaction {
+            version: 1,
+            lock_time: PackedLockTime(0),
+            input: std::iter::once(challenge)
+                .chain(inputs.iter().cloned())
+                .map(input)
+                .collect(),
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_merge_proofs() {
+        let merged = merge_proofs(vec![
+            get_test_proof(0, &[1, 2], 3000),
+            get_test_proof(0, &[3], 2000),
+        ])
+        .unwrap();
+        assert_eq!(merged.unsigned_tx.input.len(), 4);
+        assert_eq!(merged.inputs.len(), 4);
+        assert_eq!(merged.unsigned_tx.output.len(), 1);
+        assert_eq!(merged.unsigned_tx.output[0].value, 5000);
+        let inputs: Vec<OutPoint> = proof_inputs(&merged).collect();
+        assert_eq!(
+            inputs[2],
+            OutPoint::new(Txid::from_slice(&[3; 32]).unwrap(), 0)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "proofs have different challenge inputs")]
+    fn test_merge_proofs_should_fail_for_different_challenges() {
+        merge_proofs(vec![
+            get_test_proof(0, &[1], 1000),
+            get_test_proof(9, &[2], 1000),
+        ])
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "belongs to more than one wallet")]
+    fn test_merge_proofs_should_fail_for_shared_outputs() {
+        merge_proofs(vec![
+            get_test_proof(0, &[1], 1000),
+            get_test_proof(0, &[1], 1000),
+        ])
+        .unwrap();
+    }
+
+    /// Returns a valid proof spending anyone-can-spend outputs with the values, along with the
+    /// outputs
+    fn get_test_offline_proof(message: &str, values: &[u64]) -> (String, Vec<ProofUtxo>) {
+        let anyone_can_spend = Script::from(vec![OP_TRUE.to_u8()]);
+        let challenge = OutPoint::new(
+            Txid::from_hash(sha256d::Hash::hash(
+                format!("Proof-of-Reserves: {}", message).as_bytes(),
+            )),
+            0,
+        );
+        let utxos: Vec<ProofUtxo> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| ProofUtxo {
+                outpoint: OutPoint::new(Txid::from_slice(&[i as u8 + 1; 32]).unwrap(), 0),
+                value: *value,
+                script_pubkey: anyone_can_spend.clone(),
+            })
+            .collect();
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+            version: 1,
+            lock_time: PackedLockTime(0),
+            input: std::iter::once(challenge)
+                .chain(utxos.iter().map(|utxo| utxo.outpoint))
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    script_sig: Script::new(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: values.iter().sum(),
+                script_pubkey: Address {
+                    payload: Payload::PubkeyHash(PubkeyHash::from_hash(hash160::Hash::hash(&[0]))),
+                    network: Network::Testnet,
+                }
+                .script_pubkey(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: 0,
+            script_pubkey: anyone_can_spend.clone(),
+        });
+        for (input, utxo) in psbt.inputs.iter_mut().skip(1).zip(utxos.iter()) {
+            input.witness_utxo = Some(TxOut {
+                value: utxo.value,
+                script_pubkey: utxo.script_pubkey.clone(),
+            });
+            input.final_script_sig = Some(Script::new());
+        }
+        (base64::encode(consensus::serialize(&psbt)), utxos)
+    }
+
+    #[test]
+    fn test_proof_of_reserves_verification() {
+        let (psbt, utxos) = get_test_offline_proof("test", &[1000, 2000]);
+        let psbt: PartiallySignedTransaction =
+            consensus::deserialize(&base64::decode(psbt).unwrap()).unwrap();
+        let outpoints: Vec<(OutPoint, TxOut)> = utxos
+            .iter()
+            .map(|utxo| {
+                (
+                    utxo.outpoint,
+                    TxOut {
+                        value: utxo.value,
+                        script_pubkey: utxo.script_pubkey.clone(),
+                    },
+                )
+            })
+            .collect();
+        // The first output was spent after the block height of the proof
+        let unspent = outpoints.iter().skip(1).cloned().collect();
+        let verification = ProofOfReservesVerification::verify(
+            &psbt,
+            "test",
+            Network::Testnet,
+            Some(100),
+            ProofUtxos::Wallet {
+                outpoints: outpoints.clone(),
+                unspent,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            verification.challenge_txid,
+            challenge_input(&psbt).unwrap().txid
+        );
+        assert_eq!(verification.message, "test");
+        assert_eq!(verification.amount, 3000);
+        assert_eq!(verification.block_height, Some(100));
+        assert_eq!(verification.utxo_source, ProofUtxoSource::Wallet);
+        assert_eq!(verification.inputs.len(), 2);
+        assert_eq!(verification.inputs[0].value, 1000);
+        assert_eq!(verification.inputs[0].unspent, Some(false));
+        assert_eq!(verification.inputs[1].unspent, Some(true));
+        assert_eq!(verification.spent_inputs, vec![outpoints[0].0]);
+        assert_eq!(verification.unspent_amount, Some(2000));
+    }
+
+    #[test]
+    fn test_verify_proof_offline_with_snapshot() {
+        let (psbt, utxos) = get_test_offline_proof("test", &[1000, 2000]);
+        let outpoints: Vec<OutPoint> = utxos.iter().map(|utxo| utxo.outpoint).collect();
+        let proof = OfflineProofOfReserves {
+            psbt,
+            message: "test".to_string(),
+            utxos: Some(utxos),
+            block_height: Some(100),
+        };
+        let verification = verify_proof_offline(&proof, Network::Testnet).unwrap();
+        assert_eq!(verification.amount, 3000);
+        assert_eq!(verification.block_height, Some(100));
+        assert_eq!(verification.utxo_source, ProofUtxoSource::Snapshot);
+        assert_eq!(
+            verification.trust_assumption,
+            ProofUtxoSource::Snapshot.trust_assumption()
+        );
+        assert_eq!(verification.unspent_amount, Some(3000));
+        assert!(verification.spent_inputs.is_empty());
+        assert_eq!(verification.inputs.len(), 2);
+        assert_eq!(verification.inputs[1].outpoint, outpoints[1]);
+        assert_eq!(verification.inputs[1].value, 2000);
+        assert_eq!(verification.inputs[1].unspent, Some(true));
+    }
+
+    #[test]
+    fn test_verify_proof_offline_with_psbt_utxos() {
+        let (psbt, _) = get_test_offline_proof("test", &[1000, 2000]);
+        let proof = OfflineProofOfReserves {
+            psbt,
+            message: "test".to_string(),
+            utxos: None,
+            block_height: None,
+        };
+        let verification = verify_proof_offline(&proof, Network::Testnet).unwrap();
+        assert_eq!(verification.amount, 3000);
+        assert_eq!(verification.utxo_source, ProofUtxoSource::Psbt);
+        assert_eq!(verification.unspent_amount, None);
+        assert!(verification
+            .inputs
+            .iter()
+            .all(|input| input.unspent.is_none()));
+    }
+
+    #[test]
+    fn test_verify_proof_offline_with_incomplete_snapshot() {
+        let (psbt, mut utxos) = get_test_offline_proof("test", &[1000, 2000]);
+        utxos.pop();
+        let proof = OfflineProofOfReserves {
+            psbt,
+            message: "test".to_string(),
+            utxos: Some(utxos),
+            block_height: None,
+        };
+        let err = verify_proof_offline(&proof, Network::Testnet).unwrap_err();
+        assert_eq!(
+            err.reason(),
+            Some(&ProofFailure::NonSpendableInput { input_index: 2 })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "utxo data not found for input: 1")]
+    fn test_verify_proof_offline_should_fail_without_utxo_data() {
+        let (psbt, _) = get_test_offline_proof("test", &[1000]);
+        let mut psbt: PartiallySignedTransaction =
+            consensus::deserialize(&base64::decode(psbt).unwrap()).unwrap();
+        psbt.inputs[1].witness_utxo = None;
+        let proof = OfflineProofOfReserves {
+            psbt: base64::encode(consensus::serialize(&psbt)),
+            message: "test".to_string(),
+            utxos: None,
+            block_height: None,
+        };
+        verify_proof_offline(&proof, Network::Testnet).unwrap();
+    }
+}
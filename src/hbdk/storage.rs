@@ -0,0 +1,194 @@
+use crate::hbdk::errors::Error;
+use lazy_static::lazy_static;
+use regex::Regex;
+use rocket::serde::json::serde_json;
+use rocket::serde::{DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    /// Lock of each document, held while the document is written or updated
+    static ref DOCUMENT_LOCKS: Mutex<HashMap<PathBuf, Arc<Mutex<()>>>> = Mutex::new(HashMap::new());
+}
+
+static TMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Stores JSON documents in the local file system, each document is saved in its own file
+/// under the directory of its collection
+pub struct Storage {
+    path: PathBuf,
+}
+
+impl Storage {
+    pub fn new(path: &str) -> Self {
+        Storage {
+            path: PathBuf::from(path),
+        }
+    }
+
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        collection: &str,
+        key: &str,
+    ) -> Result<Option<T>, Error> {
+        Self::read(&self.document_path(collection, key)?)
+    }
+
+    pub fn save<T: Serialize>(
+        &self,
+        collection: &str,
+        key: &str,
+        document: &T,
+    ) -> Result<(), Error> {
+        let path = self.document_path(collection, key)?;
+        let lock = Self::document_lock(&path);
+        let _guard = lock.lock().unwrap_or_else(|err| err.into_inner());
+        self.write(collection, &path, document)
+    }
+
+    /// Loads the document and saves it once updated, the document is locked in between so that
+    /// concurrent updates are not lost. The document is not saved if the update fails or leaves
+    /// no document
+    pub fn update<T, R, F>(&self, collection: &str, key: &str, update: F) -> Result<R, Error>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(&mut Option<T>) -> Result<R, Error>,
+    {
+        let path = self.document_path(collection, key)?;
+        let lock = Self::document_lock(&path);
+        let _guard = lock.lock().unwrap_or_else(|err| err.into_inner());
+        let mut document = Self::read(&path)?;
+        let result = update(&mut document)?;
+        if let Some(document) = &document {
+            self.write(collection, &path, document)?;
+        }
+        Ok(result)
+    }
+
+    fn read<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Error> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    fn write<T: Serialize>(
+        &self,
+        collection: &str,
+        path: &Path,
+        document: &T,
+    ) -> Result<(), Error> {
+        fs::create_dir_all(self.path.join(collection))?;
+        // Writes to a temporary file first so that a failed write does not corrupt the document,
+        // the name is unique so that concurrent writers never share it
+        let tmp_path = path.with_extension(format!(
+            "json.{}.{}.tmp",
+            process::id(),
+            TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp_path, serde_json::to_vec_pretty(document)?)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    fn document_lock(path: &Path) -> Arc<Mutex<()>> {
+        let mut locks = DOCUMENT_LOCKS.lock().unwrap_or_else(|err| err.into_inner());
+        locks.entry(path.to_path_buf()).or_default().clone()
+    }
+
+    fn document_path(&self, collection: &str, key: &str) -> Result<PathBuf, Error> {
+        lazy_static! {
+            static ref KEY_RE: Regex = Regex::new(r"^[a-zA-Z\d_-]{1,128}$").unwrap();
+        }
+        if !KEY_RE.is_match(collection) || !KEY_RE.is_match(key) {
+            return Err(Error::new(&format!(
+                "invalid storage key, collection: {}, key: {}",
+                collection, key
+            )));
+        }
+        Ok(self.path.join(collection).join(format!("{}.json", key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_storage_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("bdk-services-storage-{}", std::process::id()));
+        let storage = Storage::new(path.to_str().unwrap());
+        let loaded: Option<BTreeMap<String, u64>> = storage.load("test", "doc").unwrap();
+        assert!(loaded.is_none());
+
+        let mut document = BTreeMap::new();
+        document.insert("balance".to_string(), 1000u64);
+        storage.save("test", "doc", &document).unwrap();
+        let loaded: Option<BTreeMap<String, u64>> = storage.load("test", "doc").unwrap();
+        assert_eq!(loaded, Some(document));
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_storage_concurrent_updates() {
+        let path = std::env::temp_dir().join(format!(
+            "bdk-services-storage-updates-{}",
+            std::process::id()
+        ));
+        let storage = Arc::new(Storage::new(path.to_str().unwrap()));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        storage
+                            .update("test", "counter", |counter: &mut Option<u64>| {
+                                *counter = Some(counter.unwrap_or(0) + 1);
+                                Ok(())
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let counter: Option<u64> = storage.load("test", "counter").unwrap();
+        assert_eq!(counter, Some(200));
+        assert_eq!(fs::read_dir(path.join("test")).unwrap().count(), 1);
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_storage_update_should_not_save_failed_update() {
+        let path = std::env::temp_dir().join(format!(
+            "bdk-services-storage-failed-update-{}",
+            std::process::id()
+        ));
+        let storage = Storage::new(path.to_str().unwrap());
+        storage.save("test", "doc", &1u64).unwrap();
+        let result = storage.update("test", "doc", |doc: &mut Option<u64>| {
+            *doc = Some(2);
+            Err::<(), _>(Error::new("update failed"))
+        });
+        assert!(result.is_err());
+        let loaded: Option<u64> = storage.load("test", "doc").unwrap();
+        assert_eq!(loaded, Some(1));
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid storage key")]
+    fn test_storage_should_fail_for_invalid_key() {
+        let storage = Storage::new("data");
+        storage.save("test", "../doc", &0).unwrap();
+    }
+}
@@ -1,11 +1,86 @@
 use xyzpub;
 use crate::hbdk::errors::Error;
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use bitcoin::util::misc::{signed_msg_hash, MessageSignature};
+use bitcoin::util::base58;
+use bitcoin::Network;
+use core::str::FromStr;
+use rocket::serde::{Deserialize, Serialize};
 
 const TESTNET_XPUB_MULTISIG_PREFIXES: [&str;2] = ["Upub", "Vpub"];
 const TESTNET_XPUB_PREFIXES: [&str;5] = ["tpub", "upub", "vpub", "Upub", "Vpub"];
 const MAINNET_XPUB_MULTISIG_PREFIXES: [&str;2] = ["Ypub", "Zpub"];
 const MAINNET_XPUB_PREFIXES: [&str;5] = ["xpub", "ypub", "zpub", "Ypub", "Zpub"];
 
+/// Script type of the keys of an extended public key version, as defined by SLIP-132
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptType {
+  /// P2PKH or P2SH
+  Legacy,
+  P2shP2wpkh,
+  P2wpkh,
+  P2shP2wsh,
+  P2wsh,
+}
+
+/// Prefix, network and script type of each SLIP-132 extended public key version
+const XPUB_VERSIONS: [(&str, Network, ScriptType); 10] = [
+  ("xpub", Network::Bitcoin, ScriptType::Legacy),
+  ("ypub", Network::Bitcoin, ScriptType::P2shP2wpkh),
+  ("zpub", Network::Bitcoin, ScriptType::P2wpkh),
+  ("Ypub", Network::Bitcoin, ScriptType::P2shP2wsh),
+  ("Zpub", Network::Bitcoin, ScriptType::P2wsh),
+  ("tpub", Network::Testnet, ScriptType::Legacy),
+  ("upub", Network::Testnet, ScriptType::P2shP2wpkh),
+  ("vpub", Network::Testnet, ScriptType::P2wpkh),
+  ("Upub", Network::Testnet, ScriptType::P2shP2wsh),
+  ("Vpub", Network::Testnet, ScriptType::P2wsh),
+];
+
+/// Length of a serialized extended key, without the checksum
+const XKEY_LENGTH: usize = 78;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct XpubDetails {
+  pub xpub: String,
+  /// SLIP-132 version prefix, e.g. Zpub
+  pub version: String,
+  /// Testnet versions are shared by testnet, signet and regtest
+  pub network: Network,
+  pub script_type: ScriptType,
+}
+
+/// Validates the checksum of the xpub and returns its version details, the version is detected
+/// from the version bytes
+pub fn get_xpub_details(xpub: &str) -> Result<XpubDetails, Error> {
+  let bytes = base58::from_check(xpub)
+    .map_err(|err| Error::new(&format!("invalid xpub: {}, {}", xpub, err)))?;
+  if bytes.len() != XKEY_LENGTH {
+    return Err(Error::new(&format!("invalid xpub length: {}, xpub: {}", bytes.len(), xpub)));
+  }
+  for (prefix, network, script_type) in XPUB_VERSIONS {
+    if bytes[..4] == xyzpub::Version::from_str(prefix)?.bytes() {
+      return Ok(XpubDetails {
+        xpub: xpub.to_string(),
+        version: prefix.to_string(),
+        network,
+        script_type,
+      });
+    }
+  }
+  Err(Error::new(&format!("unknown xpub version, xpub: {}", xpub)))
+}
+
+/// Converts the xpub to the SLIP-132 version with the prefix, e.g. tpub, Vpub
+pub fn convert_xpub(xpub: &str, version: &str) -> Result<XpubDetails, Error> {
+  get_xpub_details(xpub)?;
+  if !XPUB_VERSIONS.iter().any(|(prefix, _, _)| *prefix == version) {
+    return Err(Error::new(&format!("unknown xpub version: {}", version)));
+  }
+  get_xpub_details(&xyzpub::convert_version(xpub, &xyzpub::Version::from_str(version)?)?)
+}
+
 pub fn to_legacy_xpub(xpub: &str)-> Result<String, Error>{
   Ok(xyzpub::convert_version(xpub, &get_legacy_version(xpub)?)?)
 }
@@ -15,23 +90,39 @@ pub fn to_segwit_native_multisig_xpub(xpub: &str)-> Result<String, Error>{
 }
 
 pub fn get_segwit_native_multisig_version(xpub: &str) -> Result<xyzpub::Version, Error> {
-  if is_testnet_xpub(xpub) {
-    Ok(xyzpub::Version::VpubMultisig)
-  } else if is_mainnet_xpub(xpub) {
-    Ok(xyzpub::Version::ZpubMultisig)
-  } else {
-    Err(Error::new("Unknown xpub version"))
+  match get_xpub_network(xpub)? {
+    Network::Bitcoin => Ok(xyzpub::Version::ZpubMultisig),
+    _ => Ok(xyzpub::Version::VpubMultisig),
   }
 }
 
 pub fn get_legacy_version(xpub: &str) -> Result<xyzpub::Version, Error> {
-  if is_testnet_xpub(xpub) {
-    Ok(xyzpub::Version::Tpub)
-  } else if is_mainnet_xpub(xpub) {
-    Ok(xyzpub::Version::Xpub)
-  } else {
-    Err(Error::new("Unknown xpub version"))
+  match get_xpub_network(xpub)? {
+    Network::Bitcoin => Ok(xyzpub::Version::Xpub),
+    _ => Ok(xyzpub::Version::Tpub),
+  }
+}
+
+/// Returns the network of the xpub based on its SLIP-132 version, testnet versions are shared by
+/// testnet, signet and regtest
+pub fn get_xpub_network(xpub: &str) -> Result<Network, Error> {
+  Ok(get_xpub_details(xpub)?.network)
+}
+
+/// Returns true if keys and addresses of the first network are valid for the second one
+pub fn is_same_network(network: Network, other: Network) -> bool {
+  (network == Network::Bitcoin) == (other == Network::Bitcoin)
+}
+
+pub fn verify_xpub_network(xpub: &str, network: Network) -> Result<(), Error> {
+  let xpub_network = get_xpub_network(xpub)?;
+  if !is_same_network(xpub_network, network) {
+    return Err(Error::new(&format!(
+      "network mismatch, xpub: {} is for network: {}, expected: {}",
+      xpub, xpub_network, network
+    )));
   }
+  Ok(())
 }
 
 pub fn is_testnet_xpub(xpub: &str) -> bool {
@@ -51,23 +142,162 @@ pub fn is_mainnet_multisig_xpub(xpub: &str) -> bool {
 }
 
 pub fn is_multisig_xpub(xpub: &str) -> bool {
-  return is_mainnet_multisig_xpub(xpub) || is_testnet_multisig_xpub(xpub)
+  is_mainnet_multisig_xpub(xpub) || is_testnet_multisig_xpub(xpub)
 }
 
-fn has_prefix(value: &str, prefixes: &[&str]) -> bool {
-  for prefix in prefixes {
-    if value.starts_with(prefix) {
-      return true
-    }
+/// Checks that the base64 encoded bitcoin signed message signature was generated by the private key of the
+/// public key
+pub fn verify_message_signature(public_key: &PublicKey, message: &str, signature: &str) -> Result<bool, Error> {
+  Ok(recover_message_signer(message, signature)? == *public_key)
+}
+
+/// Returns the public key that generated the base64 encoded bitcoin signed message signature
+pub fn recover_message_signer(message: &str, signature: &str) -> Result<PublicKey, Error> {
+  let signature = base64::decode(signature)
+    .map_err(|err| Error::new(&format!("invalid signature encoding: {}", err)))?;
+  let signature = MessageSignature::from_slice(&signature)
+    .map_err(|err| Error::new(&format!("invalid message signature: {}", err)))?;
+  let secp = Secp256k1::verification_only();
+  let recovered = signature
+    .recover_pubkey(&secp, signed_msg_hash(message))
+    .map_err(|err| Error::new(&format!("failed to recover public key from signature: {}", err)))?;
+  Ok(recovered.inner)
+}
+
+/// Formats the unix timestamp as an ISO 8601 UTC date time, e.g. 2022-04-16T21:05:27Z
+pub fn format_timestamp(timestamp: u64) -> String {
+  let days = (timestamp / 86400) as i64;
+  let seconds = timestamp % 86400;
+  // Civil date from the number of days since 1970-01-01, see
+  // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+    year,
+    month,
+    day,
+    seconds / 3600,
+    seconds % 3600 / 60,
+    seconds % 60
+  )
+}
+
+/// Quotes the CSV field when it contains a separator, a quote or a line break. Fields starting
+/// with a formula character are prefixed with a quote so that spreadsheets show them as text
+pub fn csv_field(value: &str) -> String {
+  let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+    format!("'{}", value)
+  } else {
+    value.to_string()
+  };
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value
   }
-  return false
+}
+
+fn has_prefix(value: &str, prefixes: &[&str]) -> bool {
+  prefixes.iter().any(|prefix| value.starts_with(prefix))
 }
 
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
 
   use super::*;
+  const TPUB: &str = "tpubD9zJG3Z4c9LLBCTeEcq64yFtVtfDHffWspDxKLY3apTbu4ocjFoD4vXz4XV2tfMAEQ8p9Km6CiEHBYqVhhG3qPPEcBZqPnwYuWx9RVmiVLz";
+
+  fn all_versions() -> Vec<(xyzpub::Version, Network)> {
+    vec![
+      (xyzpub::Version::Xpub, Network::Bitcoin),
+      (xyzpub::Version::Ypub, Network::Bitcoin),
+      (xyzpub::Version::Zpub, Network::Bitcoin),
+      (xyzpub::Version::YpubMultisig, Network::Bitcoin),
+      (xyzpub::Version::ZpubMultisig, Network::Bitcoin),
+      (xyzpub::Version::Tpub, Network::Testnet),
+      (xyzpub::Version::Upub, Network::Testnet),
+      (xyzpub::Version::Vpub, Network::Testnet),
+      (xyzpub::Version::UpubMultisig, Network::Testnet),
+      (xyzpub::Version::VpubMultisig, Network::Testnet),
+    ]
+  }
+
+  #[test]
+  fn test_get_xpub_network() {
+    for (version, network) in all_versions() {
+      let xpub = xyzpub::convert_version(TPUB, &version).unwrap();
+      assert_eq!(get_xpub_network(&xpub).unwrap(), network, "xpub: {}", xpub);
+    }
+    assert!(get_xpub_network("xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi").is_err());
+  }
+
+  #[test]
+  fn test_get_xpub_details() {
+    for (prefix, network, script_type) in XPUB_VERSIONS {
+      let xpub = xyzpub::convert_version(TPUB, &xyzpub::Version::from_str(prefix).unwrap()).unwrap();
+      let details = get_xpub_details(&xpub).unwrap();
+      assert_eq!(details.version, prefix);
+      assert_eq!(details.network, network);
+      assert_eq!(details.script_type, script_type);
+      assert_eq!(details.xpub, xpub);
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "invalid xpub")]
+  fn test_get_xpub_details_should_fail_for_invalid_checksum() {
+    let mut xpub = TPUB.to_string();
+    xpub.pop();
+    xpub.push('a');
+    get_xpub_details(&xpub).unwrap();
+  }
+
+  #[test]
+  #[should_panic(expected = "unknown xpub version")]
+  fn test_get_xpub_details_should_fail_for_private_key() {
+    get_xpub_details("tprv8ZgxMBicQKsPeDgjzdC36fs6bMjGApWDNLR9erAXMs5skhMv36j9MV5ecvfavji5khqjWaWSFhN3YcCUUdiKH6isR4Pwy3U5y5egddBr16m").unwrap();
+  }
+
+  #[test]
+  fn test_convert_xpub() {
+    for (prefix, _, _) in XPUB_VERSIONS {
+      let converted = convert_xpub(TPUB, prefix).unwrap();
+      assert!(converted.xpub.starts_with(prefix));
+      assert_eq!(convert_xpub(&converted.xpub, "tpub").unwrap().xpub, TPUB);
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "unknown xpub version: Vprv")]
+  fn test_convert_xpub_should_fail_for_unknown_version() {
+    convert_xpub(TPUB, "Vprv").unwrap();
+  }
+
+  #[test]
+  fn test_verify_xpub_network() {
+    for (version, xpub_network) in all_versions() {
+      let xpub = xyzpub::convert_version(TPUB, &version).unwrap();
+      for network in [Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+        let result = verify_xpub_network(&xpub, network);
+        if is_same_network(xpub_network, network) {
+          assert!(result.is_ok(), "xpub: {} network: {}", xpub, network);
+        } else {
+          assert!(result.unwrap_err().to_string().starts_with("network mismatch"), "xpub: {} network: {}", xpub, network);
+        }
+      }
+    }
+  }
+
   #[test]
   fn test_is_testnet_xpub() {
     assert_eq!(is_testnet_xpub("vpub5UJtN2FGcxFk32jUXSSFV2keY3qNYWawtiHgNJLVnNTtYPpT47SvMm7Q9MiBQHVP5VE9rRask1mZzMDmw8f6XZhSwMx85TNNAyJwwqHDbgy"), true);
@@ -100,4 +330,24 @@ mod tests {
     assert_eq!(is_multisig_xpub("zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi"), false);
     assert_eq!(is_multisig_xpub("Zpub75bKLk9fCjgfELzLr2XS5TEcCXXGrci4EDwAcppFNBDwpNy53JhJS8cbRjdv39noPDKSfzK7EPC1Ciyfb7jRwY7DmiuYJ6WDr2nEL6yTkHi"), true);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_format_timestamp() {
+    assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_timestamp(1650143127), "2022-04-16T21:05:27Z");
+    assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+    assert_eq!(format_timestamp(1704067199), "2023-12-31T23:59:59Z");
+  }
+
+  #[test]
+  fn test_csv_field() {
+    assert_eq!(csv_field("Cold storage"), "Cold storage");
+    assert_eq!(csv_field("Rent, March"), "\"Rent, March\"");
+    assert_eq!(csv_field("The \"big\" one"), "\"The \"\"big\"\" one\"");
+    assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+    assert_eq!(csv_field("+1"), "'+1");
+    assert_eq!(csv_field("-1"), "'-1");
+    assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+    assert_eq!(csv_field("Fee 1+1"), "Fee 1+1");
+  }
+}
@@ -0,0 +1,667 @@
+use crate::hbdk::errors::Error;
+use crate::hbdk::{util, Cosigner, Descriptors, Multisig};
+use bdk::descriptor::{checksum, Descriptor, DescriptorPublicKey};
+use core::str::FromStr;
+use lazy_static::lazy_static;
+use regex::Regex;
+use rocket::serde::json::{serde_json, Value};
+use rocket::serde::{Deserialize, Serialize};
+
+/// Wallet configuration file formats used by hardware and desktop wallets to describe a
+/// multisig setup
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WalletConfigFormat {
+    /// Coldcard multisig text file ("Name:/Policy:/Derivation:/XFP: xpub")
+    Coldcard,
+    /// Specter Desktop JSON backup, with the output descriptor in the `descriptor` field
+    Specter,
+    /// Sparrow export, either the Specter compatible JSON or the output descriptor text file
+    Sparrow,
+    /// Caravan JSON wallet configuration
+    Caravan,
+    /// Bitcoin Core `importdescriptors` JSON request
+    #[serde(rename = "bitcoin-core")]
+    BitcoinCore,
+}
+
+/// Multisig setup read from a wallet configuration file
+#[derive(Debug)]
+pub struct WalletConfig {
+    pub name: Option<String>,
+    pub multisig: Multisig,
+}
+
+/// Parses a wallet configuration file, the format is detected from the content when not provided
+pub fn import(content: &str, format: Option<WalletConfigFormat>) -> Result<WalletConfig, Error> {
+    let format = match format {
+        Some(format) => format,
+        None => detect_format(content)?,
+    };
+    match format {
+        WalletConfigFormat::Coldcard => parse_coldcard(content),
+        WalletConfigFormat::Specter => parse_specter(content),
+        WalletConfigFormat::Sparrow => {
+            if content.trim_start().starts_with('{') {
+                parse_specter(content)
+            } else {
+                parse_descriptor_file(content)
+            }
+        }
+        WalletConfigFormat::Caravan => parse_caravan(content),
+        WalletConfigFormat::BitcoinCore => parse_bitcoin_core(content),
+    }
+}
+
+/// Generates the wallet configuration file used to register the multisig on a hardware or
+/// desktop wallet
+pub fn export(
+    format: WalletConfigFormat,
+    name: &str,
+    multisig: &Multisig,
+    descriptors: &Descriptors,
+) -> Result<String, Error> {
+    match format {
+        WalletConfigFormat::Coldcard => export_coldcard(name, multisig),
+        WalletConfigFormat::Specter | WalletConfigFormat::Sparrow => {
+            export_specter(name, multisig, descriptors)
+        }
+        WalletConfigFormat::Caravan => export_caravan(name, multisig),
+        WalletConfigFormat::BitcoinCore => export_bitcoin_core(descriptors),
+    }
+}
+
+pub fn detect_format(content: &str) -> Result<WalletConfigFormat, Error> {
+    if content.trim_start().starts_with('[') {
+        Ok(WalletConfigFormat::BitcoinCore)
+    } else if content.trim_start().starts_with('{') {
+        let value: Value = serde_json::from_str(content)?;
+        if value.get("quorum").is_some() {
+            Ok(WalletConfigFormat::Caravan)
+        } else if value.get("descriptor").is_some() {
+            Ok(WalletConfigFormat::Specter)
+        } else {
+            Err(Error::new("unknown wallet configuration json format"))
+        }
+    } else if config_lines(content).any(|line| line.starts_with("wsh(")) {
+        Ok(WalletConfigFormat::Sparrow)
+    } else {
+        Ok(WalletConfigFormat::Coldcard)
+    }
+}
+
+/// Builds a Multisig from a `wsh(sortedmulti(...))` output descriptor, the checksum is verified
+/// when present. A BIP-389 `<0;1>` step is read as the receive descriptor
+pub fn multisig_from_descriptor(descriptor: &str) -> Result<Multisig, Error> {
+    let mut descriptor = descriptor.trim().to_string();
+    if descriptor.contains("/<0;1>/") {
+        // miniscript does not parse multipath keys, so the checksum is verified on the original
+        checksum::calc_checksum(&descriptor).map_err(bdk::Error::from)?;
+        descriptor = descriptor
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .replace("/<0;1>/", "/0/");
+    }
+    Multisig::from_descriptor(&Descriptor::<DescriptorPublicKey>::from_str(&descriptor)?)
+}
+
+fn new_cosigner(xfp: &str, xpub: &str, derivation_path: Option<&str>) -> Result<Cosigner, Error> {
+    lazy_static! {
+        static ref XFP_RE: Regex = Regex::new(r"^[a-fA-F\d]{8}$").unwrap();
+    }
+    if !XFP_RE.is_match(xfp) {
+        return Err(Error::new(&format!("invalid xfp: {}", xfp)));
+    }
+    let mut cosigner = Cosigner::from_str(&util::to_segwit_native_multisig_xpub(xpub)?)?;
+    cosigner.xfp = Some(xfp.to_lowercase());
+    cosigner.derivation_path = derivation_path.map(normalize_derivation_path);
+    Ok(cosigner)
+}
+
+/// Uses the `'` hardened marker, wallets also use `h` or `H`
+fn normalize_derivation_path(path: &str) -> String {
+    lazy_static! {
+        static ref HARDENED_RE: Regex = Regex::new(r"(\d)[hH]").unwrap();
+    }
+    HARDENED_RE.replace_all(path, "$1'").to_string()
+}
+
+fn config_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn parse_coldcard(content: &str) -> Result<WalletConfig, Error> {
+    let mut name = None;
+    let mut policy = None;
+    let mut derivation_path: Option<String> = None;
+    let mut cosigners = Vec::new();
+    for line in config_lines(content) {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| Error::new(&format!("invalid coldcard multisig file line: {}", line)))?;
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "name" => name = Some(value.to_string()),
+            "policy" => policy = Some(parse_policy(value)?),
+            "derivation" => derivation_path = Some(value.to_string()),
+            "format" => {
+                if !value.eq_ignore_ascii_case("P2WSH") {
+                    return Err(Error::new(&format!(
+                        "unsupported multisig format: {}, only P2WSH is supported",
+                        value
+                    )));
+                }
+            }
+            xfp => cosigners.push(new_cosigner(xfp, value, derivation_path.as_deref())?),
+        }
+    }
+    let (threshold, total) =
+        policy.ok_or_else(|| Error::new("coldcard multisig file is missing the policy"))?;
+    if total as usize != cosigners.len() {
+        return Err(Error::new(&format!(
+            "policy expects {} cosigners, found: {}",
+            total,
+            cosigners.len()
+        )));
+    }
+    let mut multisig = Multisig::new(threshold);
+    for cosigner in cosigners {
+        multisig.add_cosigner(cosigner);
+    }
+    Ok(WalletConfig { name, multisig })
+}
+
+/// Parses the "M of N" or "M/N" policy
+fn parse_policy(policy: &str) -> Result<(u32, u32), Error> {
+    let parts: Vec<&str> = policy
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .filter(|part| !part.is_empty() && !part.eq_ignore_ascii_case("of"))
+        .collect();
+    if let [threshold, total] = parts.as_slice() {
+        if let (Ok(threshold), Ok(total)) = (threshold.parse(), total.parse()) {
+            return Ok((threshold, total));
+        }
+    }
+    Err(Error::new(&format!("invalid multisig policy: {}", policy)))
+}
+
+fn parse_specter(content: &str) -> Result<WalletConfig, Error> {
+    let value: Value = serde_json::from_str(content)?;
+    let descriptor = value
+        .get("descriptor")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::new("wallet configuration is missing the descriptor"))?;
+    Ok(WalletConfig {
+        name: value.get("label").and_then(Value::as_str).map(String::from),
+        multisig: multisig_from_descriptor(descriptor)?,
+    })
+}
+
+fn parse_descriptor_file(content: &str) -> Result<WalletConfig, Error> {
+    let descriptor = config_lines(content)
+        .find(|line| line.starts_with("wsh("))
+        .ok_or_else(|| Error::new("wallet configuration is missing the descriptor"))?;
+    Ok(WalletConfig {
+        name: None,
+        multisig: multisig_from_descriptor(descriptor)?,
+    })
+}
+
+fn parse_bitcoin_core(content: &str) -> Result<WalletConfig, Error> {
+    let requests: Vec<Value> = serde_json::from_str(content)?;
+    let descriptor = requests
+        .iter()
+        .find(|request| request.get("internal").and_then(Value::as_bool) != Some(true))
+        .and_then(|request| request.get("desc"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::new("wallet configuration is missing the descriptor"))?;
+    Ok(WalletConfig {
+        name: None,
+        multisig: multisig_from_descriptor(descriptor)?,
+    })
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CaravanConfig {
+    name: Option<String>,
+    address_type: String,
+    network: Option<String>,
+    client: Option<Value>,
+    quorum: CaravanQuorum,
+    extended_public_keys: Vec<CaravanExtendedPublicKey>,
+    starting_address_index: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CaravanQuorum {
+    required_signers: u32,
+    total_signers: u32,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CaravanExtendedPublicKey {
+    name: Option<String>,
+    xpub: String,
+    xfp: Option<String>,
+    bip32_path: Option<String>,
+    method: Option<String>,
+}
+
+fn parse_caravan(content: &str) -> Result<WalletConfig, Error> {
+    let config: CaravanConfig = serde_json::from_str(content)?;
+    if !config.address_type.eq_ignore_ascii_case("P2WSH") {
+        return Err(Error::new(&format!(
+            "unsupported address type: {}, only P2WSH is supported",
+            config.address_type
+        )));
+    }
+    if config.quorum.total_signers as usize != config.extended_public_keys.len() {
+        return Err(Error::new(&format!(
+            "quorum expects {} cosigners, found: {}",
+            config.quorum.total_signers,
+            config.extended_public_keys.len()
+        )));
+    }
+    let mut multisig = Multisig::new(config.quorum.required_signers);
+    for key in &config.extended_public_keys {
+        let cosigner = match &key.xfp {
+            Some(xfp) => new_cosigner(xfp, &key.xpub, key.bip32_path.as_deref())?,
+            None => Cosigner::from_str(&util::to_segwit_native_multisig_xpub(&key.xpub)?)?,
+        };
+        multisig.add_cosigner(cosigner);
+    }
+    Ok(WalletConfig {
+        name: config.name,
+        multisig,
+    })
+}
+
+/// Returns the xfp and derivation path of the cosigner, which are required to register the
+/// multisig on a hardware wallet
+fn key_origin(cosigner: &Cosigner) -> Result<(&str, &str), Error> {
+    match (&cosigner.xfp, &cosigner.derivation_path) {
+        (Some(xfp), Some(derivation_path)) => Ok((xfp, derivation_path)),
+        _ => Err(Error::new(&format!(
+            "xfp and derivation path are required to export the cosigner, xpub:{}",
+            cosigner.xpub
+        ))),
+    }
+}
+
+/// Coldcard rejects multisig names longer than 20 characters
+const COLDCARD_MAX_NAME_LEN: usize = 20;
+
+/// Returns the name of the Coldcard setup file, which must be printable ASCII, longer names are
+/// truncated to the Coldcard limit
+fn coldcard_name(name: &str) -> Result<&str, Error> {
+    let name = name.trim();
+    if name.is_empty() || !name.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        return Err(Error::new(&format!(
+            "the coldcard wallet name must be printable ascii, found: {:?}",
+            name
+        )));
+    }
+    Ok(name[..name.len().min(COLDCARD_MAX_NAME_LEN)].trim_end())
+}
+
+fn export_coldcard(name: &str, multisig: &Multisig) -> Result<String, Error> {
+    let name = coldcard_name(name)?;
+    let mut content = format!(
+        "# Coldcard Multisig setup file\n#\nName: {}\nPolicy: {} of {}\nFormat: P2WSH\n",
+        name,
+        multisig.threshold,
+        multisig.cosigners.len()
+    );
+    for cosigner in &multisig.cosigners {
+        let (xfp, derivation_path) = key_origin(cosigner)?;
+        content.push_str(&format!(
+            "\nDerivation: {}\n{}: {}\n",
+            derivation_path,
+            xfp.to_uppercase(),
+            util::to_legacy_xpub(&cosigner.xpub)?
+        ));
+    }
+    Ok(content)
+}
+
+fn export_specter(
+    name: &str,
+    multisig: &Multisig,
+    descriptors: &Descriptors,
+) -> Result<String, Error> {
+    let devices: Vec<Value> = multisig
+        .cosigners
+        .iter()
+        .map(|cosigner| {
+            serde_json::json!({
+                "type": "other",
+                "label": cosigner.xfp.clone().unwrap_or_else(|| cosigner.xpub.clone()),
+            })
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "label": name,
+        "blockheight": 0,
+        "descriptor": descriptors.descriptor,
+        "devices": devices,
+    }))?)
+}
+
+fn export_caravan(name: &str, multisig: &Multisig) -> Result<String, Error> {
+    let mut extended_public_keys = Vec::new();
+    for (i, cosigner) in multisig.cosigners.iter().enumerate() {
+        let (xfp, derivation_path) = key_origin(cosigner)?;
+        extended_public_keys.push(CaravanExtendedPublicKey {
+            name: Some(format!("Cosigner {}", i + 1)),
+            xpub: util::to_legacy_xpub(&cosigner.xpub)?,
+            xfp: Some(xfp.to_string()),
+            bip32_path: Some(derivation_path.to_string()),
+            method: Some("text".to_string()),
+        });
+    }
+    let network = if multisig
+        .cosigners
+        .iter()
+        .all(|cosigner| util::is_testnet_xpub(&cosigner.xpub))
+    {
+        "testnet"
+    } else {
+        "mainnet"
+    };
+    Ok(serde_json::to_string_pretty(&CaravanConfig {
+        name: Some(name.to_string()),
+        address_type: "P2WSH".to_string(),
+        network: Some(network.to_string()),
+        client: Some(serde_json::json!({ "type": "public" })),
+        quorum: CaravanQuorum {
+            required_signers: multisig.threshold,
+            total_signers: multisig.cosigners.len() as u32,
+        },
+        extended_public_keys,
+        starting_address_index: Some(0),
+    })?)
+}
+
+fn export_bitcoin_core(descriptors: &Descriptors) -> Result<String, Error> {
+    let mut requests = vec![serde_json::json!({
+        "desc": descriptors.descriptor,
+        "active": true,
+        "internal": false,
+        "range": [0, 999],
+        "timestamp": 0,
+    })];
+    if let Some(change_descriptor) = &descriptors.change_descriptor {
+        requests.push(serde_json::json!({
+            "desc": change_descriptor,
+            "active": true,
+            "internal": true,
+            "range": [0, 999],
+            "timestamp": 0,
+        }));
+    }
+    Ok(serde_json::to_string_pretty(&requests)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const VPUB1: &str = "Vpub5knpWjcHt8uQ7xUWM9mDRWpKst81n7zzmtr2LDaH3GPHkMoVw41L3bDDSded6xioVcg7L3ozoiwfCEKPCVFoiiKy9yqkV6nejso8Puy7Mvf";
+    const VPUB2: &str = "Vpub5mU6P8gQgQbHZWUuLexLgEjRt1WnW1KJscQSuDG9W9HbCHgTimuRG4k6ykX52HYH1uqmp832QypyKwqHkc3gVmQWoZmbXWGrYXyPS2SqHJZ";
+
+    fn with_checksum(descriptor: String) -> String {
+        let checksum = checksum::calc_checksum(&descriptor).unwrap();
+        format!("{}#{}", descriptor, checksum)
+    }
+
+    fn assert_test_multisig(multisig: &Multisig) {
+        assert_eq!(multisig.threshold, 2);
+        assert_eq!(multisig.cosigners.len(), 2);
+        assert_eq!(multisig.cosigners[0].xfp, Some("c0b82c68".to_string()));
+        assert_eq!(multisig.cosigners[0].xpub, VPUB1);
+        assert_eq!(
+            multisig.cosigners[0].derivation_path,
+            Some("m/48'/1'/0'/2'".to_string())
+        );
+        assert_eq!(multisig.cosigners[1].xfp, Some("5e6b6a06".to_string()));
+        assert_eq!(multisig.cosigners[1].xpub, VPUB2);
+    }
+
+    #[test]
+    fn test_import_coldcard() {
+        let content = format!(
+            "# Coldcard Multisig setup file\n\nName: Vault\nPolicy: 2 of 2\nDerivation: m/48'/1'/0'/2'\nFormat: P2WSH\n\nC0B82C68: {}\n5E6B6A06: {}\n",
+            util::to_legacy_xpub(VPUB1).unwrap(),
+            util::to_legacy_xpub(VPUB2).unwrap()
+        );
+        assert_eq!(
+            detect_format(&content).unwrap(),
+            WalletConfigFormat::Coldcard
+        );
+        let config = import(&content, None).unwrap();
+        assert_eq!(config.name, Some("Vault".to_string()));
+        assert_test_multisig(&config.multisig);
+    }
+
+    #[test]
+    #[should_panic(expected = "policy expects 3 cosigners, found: 2")]
+    fn test_import_coldcard_should_fail_for_policy_mismatch() {
+        let content = format!(
+            "Policy: 2 of 3\nDerivation: m/48'/1'/0'/2'\nC0B82C68: {}\n5E6B6A06: {}\n",
+            VPUB1, VPUB2
+        );
+        import(&content, Some(WalletConfigFormat::Coldcard)).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported multisig format: P2SH")]
+    fn test_import_coldcard_should_fail_for_non_p2wsh_format() {
+        let content = format!(
+            "Policy: 1 of 1\nFormat: P2SH\nDerivation: m/48'/1'/0'/2'\nC0B82C68: {}\n",
+            VPUB1
+        );
+        import(&content, None).unwrap();
+    }
+
+    #[test]
+    fn test_import_specter() {
+        let descriptor = with_checksum(format!(
+            "wsh(sortedmulti(2,[c0b82c68/48h/1h/0h/2h]{}/0/*,[5e6b6a06/48h/1h/0h/2h]{}/0/*))",
+            util::to_legacy_xpub(VPUB1).unwrap(),
+            util::to_legacy_xpub(VPUB2).unwrap()
+        ));
+        let content = format!(
+            r#"{{"label": "Vault", "blockheight": 0, "descriptor": "{}"}}"#,
+            descriptor
+        );
+        assert_eq!(
+            detect_format(&content).unwrap(),
+            WalletConfigFormat::Specter
+        );
+        let config = import(&content, None).unwrap();
+        assert_eq!(config.name, Some("Vault".to_string()));
+        assert_test_multisig(&config.multisig);
+    }
+
+    #[test]
+    fn test_import_sparrow_descriptor_file() {
+        let descriptor = with_checksum(format!(
+            "wsh(sortedmulti(2,[c0b82c68/48'/1'/0'/2']{}/<0;1>/*,[5e6b6a06/48'/1'/0'/2']{}/<0;1>/*))",
+            util::to_legacy_xpub(VPUB1).unwrap(),
+            util::to_legacy_xpub(VPUB2).unwrap()
+        ));
+        let content = format!(
+            "# Receive and change descriptor (BIP389):\n{}\n",
+            descriptor
+        );
+        assert_eq!(
+            detect_format(&content).unwrap(),
+            WalletConfigFormat::Sparrow
+        );
+        assert_test_multisig(&import(&content, None).unwrap().multisig);
+    }
+
+    #[test]
+    fn test_import_caravan() {
+        let content = format!(
+            r#"{{
+                "name": "Vault",
+                "addressType": "P2WSH",
+                "network": "testnet",
+                "client": {{"type": "public"}},
+                "quorum": {{"requiredSigners": 2, "totalSigners": 2}},
+                "extendedPublicKeys": [
+                    {{"name": "a", "bip32Path": "m/48'/1'/0'/2'", "xpub": "{}", "xfp": "c0b82c68"}},
+                    {{"name": "b", "bip32Path": "m/48'/1'/0'/2'", "xpub": "{}", "xfp": "5e6b6a06"}}
+                ],
+                "startingAddressIndex": 0
+            }}"#,
+            util::to_legacy_xpub(VPUB1).unwrap(),
+            util::to_legacy_xpub(VPUB2).unwrap()
+        );
+        assert_eq!(
+            detect_format(&content).unwrap(),
+            WalletConfigFormat::Caravan
+        );
+        let config = import(&content, None).unwrap();
+        assert_eq!(config.name, Some("Vault".to_string()));
+        assert_test_multisig(&config.multisig);
+    }
+
+    #[test]
+    #[should_panic(expected = "Wallet is not of type Pay-to-Witness-Script-Hash")]
+    fn test_multisig_from_descriptor_should_fail_for_non_sortedmulti() {
+        multisig_from_descriptor(&format!(
+            "wpkh({}/0/*)",
+            util::to_legacy_xpub(VPUB1).unwrap()
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid checksum")]
+    fn test_multisig_from_descriptor_should_fail_for_wrong_checksum() {
+        multisig_from_descriptor(&format!(
+            "wsh(sortedmulti(2,[c0b82c68/48'/1'/0'/2']{}/0/*,[5e6b6a06/48'/1'/0'/2']{}/0/*))#abcdefgh",
+            util::to_legacy_xpub(VPUB1).unwrap(),
+            util::to_legacy_xpub(VPUB2).unwrap()
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_export_coldcard_roundtrip() {
+        let multisig = import(
+            &format!(
+                "Policy: 2 of 2\nDerivation: m/48'/1'/0'/2'\nC0B82C68: {}\n5E6B6A06: {}\n",
+                VPUB1, VPUB2
+            ),
+            Some(WalletConfigFormat::Coldcard),
+        )
+        .unwrap()
+        .multisig;
+        let descriptors = Descriptors::from_descriptor(multisig.descriptor(false).unwrap());
+        let content = export(
+            WalletConfigFormat::Coldcard,
+            "Vault",
+            &multisig,
+            &descriptors,
+        )
+        .unwrap();
+        assert!(content.contains("Policy: 2 of 2\n"));
+        assert!(content.contains(&format!(
+            "C0B82C68: {}\n",
+            util::to_legacy_xpub(VPUB1).unwrap()
+        )));
+        let config = import(&content, None).unwrap();
+        assert_eq!(config.name, Some("Vault".to_string()));
+        assert_test_multisig(&config.multisig);
+    }
+
+    #[test]
+    fn test_export_caravan_roundtrip() {
+        let multisig = multisig_from_descriptor(&format!(
+            "wsh(sortedmulti(2,[c0b82c68/48'/1'/0'/2']{}/0/*,[5e6b6a06/48'/1'/0'/2']{}/0/*))",
+            util::to_legacy_xpub(VPUB1).unwrap(),
+            util::to_legacy_xpub(VPUB2).unwrap()
+        ))
+        .unwrap();
+        let descriptors = Descriptors::from_descriptor(multisig.descriptor(false).unwrap());
+        let content = export(
+            WalletConfigFormat::Caravan,
+            "Vault",
+            &multisig,
+            &descriptors,
+        )
+        .unwrap();
+        assert!(content.contains("\"network\": \"testnet\""));
+        let config = import(&content, None).unwrap();
+        assert_eq!(config.name, Some("Vault".to_string()));
+        assert_test_multisig(&config.multisig);
+    }
+
+    #[test]
+    fn test_export_bitcoin_core_roundtrip() {
+        let multisig = multisig_from_descriptor(&format!(
+            "wsh(sortedmulti(2,[c0b82c68/48'/1'/0'/2']{}/0/*,[5e6b6a06/48'/1'/0'/2']{}/0/*))",
+            util::to_legacy_xpub(VPUB1).unwrap(),
+            util::to_legacy_xpub(VPUB2).unwrap()
+        ))
+        .unwrap();
+        let descriptors = Descriptors::new(
+            multisig.descriptor(false).unwrap(),
+            multisig.descriptor(true).unwrap(),
+        );
+        let content = export(
+            WalletConfigFormat::BitcoinCore,
+            "Vault",
+            &multisig,
+            &descriptors,
+        )
+        .unwrap();
+        let requests: Vec<Value> = serde_json::from_str(&content).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["internal"], Value::Bool(true));
+        assert_eq!(
+            detect_format(&content).unwrap(),
+            WalletConfigFormat::BitcoinCore
+        );
+        assert_test_multisig(&import(&content, None).unwrap().multisig);
+    }
+
+    #[test]
+    #[should_panic(expected = "xfp and derivation path are required to export the cosigner")]
+    fn test_export_coldcard_should_fail_for_cosigner_without_origin() {
+        let mut multisig = Multisig::new(1);
+        multisig.add_cosigner(Cosigner::from_str(VPUB1).unwrap());
+        let descriptors = Descriptors::from_descriptor(multisig.descriptor(false).unwrap());
+        export(
+            WalletConfigFormat::Coldcard,
+            "Vault",
+            &multisig,
+            &descriptors,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_coldcard_name() {
+        assert_eq!(coldcard_name(" Vault ").unwrap(), "Vault");
+        assert_eq!(
+            coldcard_name("Company treasury vault 2022").unwrap(),
+            "Company treasury vau"
+        );
+        assert_eq!(coldcard_name("Cold storage vault 1").unwrap().len(), 20);
+        assert!(coldcard_name("Vault\nPolicy: 1 of 2").is_err());
+        assert!(coldcard_name("Vault\u{20ac}").is_err());
+        assert!(coldcard_name("").is_err());
+    }
+}
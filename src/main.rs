@@ -1,14 +1,37 @@
+// Rocket's codegen binds the unit return value of the `options` route
+#![allow(clippy::let_unit_value)]
 #[macro_use]
 extern crate rocket;
 
 use bdk_services::hbdk::{
-    errors::Error, Blockchain, Cosigner, Descriptors, Multisig, SignedTrx, Trx, TrxDetails,
-    VerifyPSBTPayload, Wallet,
+    bip322::{self, MessageProofRequest, MessageProofVerification, SignedMessageProof},
+    bsms::{self, BsmsSessionStatus, CreatedBsmsSession},
+    errors::Error,
+    labels::LabelStore,
+    reserves::{
+        self, AggregatedProofOfReserves, AggregatedProofOfReservesRequest,
+        AggregatedProofOfReservesVerification, OfflineProofOfReserves,
+        SignedAggregatedProofOfReserves,
+    },
+    storage::Storage,
+    wallet_config, AddressVerification, AddressesRequest, BalanceRequest, BalanceSnapshot,
+    Blockchain, BsmsDescriptorRecord, BsmsDescriptorRecordRequest, BsmsKeyRecordRequest,
+    BsmsSessionRequest, ConvertXpubRequest, ConvertedXpub, Cosigner, CosignerChallenge,
+    DerivedAddress, Descriptors, ExportTrxsRequest, ExportWalletRequest, GetTrxRequest,
+    ImportLabelsRequest, ImportWalletRequest, ImportedLabels, ImportedWallet, ListTrxsRequest,
+    Multisig, RescanRequest, RescanResult, SignedTrx, Trx, TrxDetails, TrxWithSigners,
+    VerifyAddressRequest, VerifyPSBTPayload, Wallet, WalletStatus, DEFAULT_MAX_STOP_GAP,
+    DEFAULT_STOP_GAP,
+};
+use bdk_services::hbdk::{
+    ProofOfReserves, ProofOfReservesRequest, ProofOfReservesVerification, ProofSignersStatus,
+    SignedProofOfReserves,
 };
-use bdk_services::hbdk::{ProofOfReserves, ProofOfReservesRequest, SignedProofOfReserves};
 use bitcoin::Network;
 use rocket::fairing::{AdHoc, Fairing, Info, Kind};
-use rocket::http::Header;
+use rocket::futures::stream::{self, Stream};
+use rocket::http::{ContentType, Header};
+use rocket::response::stream::TextStream;
 use rocket::serde::{json::Json, Deserialize};
 use rocket::State;
 use rocket::{Request, Response};
@@ -19,6 +42,37 @@ struct Config {
     network_url: String,
     network: Network,
     pub_key_search_radius: u8,
+    storage_path: String,
+    #[serde(default = "default_stop_gap")]
+    stop_gap: usize,
+    /// Largest stop gap a request can set, each address up to the stop gap is checked by the sync
+    #[serde(default = "default_max_stop_gap")]
+    max_stop_gap: usize,
+}
+
+fn default_stop_gap() -> usize {
+    DEFAULT_STOP_GAP
+}
+
+fn default_max_stop_gap() -> usize {
+    DEFAULT_MAX_STOP_GAP
+}
+
+impl Config {
+    /// Returns the stop gap of the wallet sync, set by the `stop_gap` query parameter of the
+    /// request up to the `max_stop_gap`, or else the `stop_gap` of the config
+    fn stop_gap(&self, stop_gap: Option<&str>) -> Result<usize, Error> {
+        match stop_gap {
+            Some(stop_gap) => match stop_gap.parse::<usize>() {
+                Ok(stop_gap) if stop_gap > 0 && stop_gap <= self.max_stop_gap => Ok(stop_gap),
+                _ => Err(Error::new(&format!(
+                    "stop_gap must be a positive integer up to {}",
+                    self.max_stop_gap
+                ))),
+            },
+            None => Ok(self.stop_gap),
+        }
+    }
 }
 
 pub struct CORS;
@@ -43,9 +97,36 @@ impl Fairing for CORS {
     }
 }
 
+#[allow(unused_variables)]
 #[options("/<path..>")]
 fn options(path: PathBuf) {}
 
+/// Loads the labels of the wallet from the storage
+/// Connects to the Electrum server, with the stop gap of the `stop_gap` query parameter
+fn new_blockchain(config: &Config, stop_gap: Option<&str>) -> Result<Blockchain, Error> {
+    Blockchain::new(
+        &config.network_url,
+        config.network,
+        config.stop_gap(stop_gap)?,
+    )
+}
+
+fn with_labels<'a>(config: &Config, wallet: Wallet<'a>) -> Result<Wallet<'a>, Error> {
+    let storage = Storage::new(&config.storage_path);
+    let labels = LabelStore::new(&storage).load(&wallet.descriptor_checksum()?)?;
+    Ok(wallet.with_labels(labels))
+}
+
+fn wallets_from_descriptors<'a>(
+    blockchain: &'a Blockchain,
+    descriptors: &[Descriptors],
+) -> Result<Vec<Wallet<'a>>, Error> {
+    descriptors
+        .iter()
+        .map(|descriptors| Wallet::from_descriptors(blockchain, descriptors))
+        .collect()
+}
+
 /// Returns a new address for the provided output descriptor
 ///
 /// # Arguments
@@ -55,18 +136,106 @@ fn options(path: PathBuf) {}
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid descriptor
-#[post("/gen_new_address", data = "<descriptors>")]
+#[post("/gen_new_address?<stop_gap>", data = "<descriptors>")]
 fn gen_new_address(
     config: &State<Config>,
+    stop_gap: Option<&str>,
     descriptors: Json<Descriptors>,
 ) -> Result<String, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &descriptors)?;
     let address = wallet.get_new_address()?;
     Ok(address.to_string())
 }
 
-/// Returns a list of trxs for the provided output descriptors
+/// Returns the addresses of the keychain in the requested index range, along with the derived public key of each
+/// cosigner
+///
+/// # Arguments
+///
+/// * `addresses_req` - An AddressesRequest object with the descriptors, the keychain: receive or change, the start
+/// index, the number of addresses and whether to sync the wallet to set the used flag of the addresses
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or index range, or if the change keychain is requested
+/// without a change descriptor
+#[post("/get_addresses?<stop_gap>", data = "<addresses_req>")]
+fn get_addresses(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    addresses_req: Json<AddressesRequest>,
+) -> Result<Json<Vec<DerivedAddress>>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = with_labels(
+        config,
+        Wallet::from_descriptors(&blockchain, &addresses_req.descriptors)?,
+    )?;
+    Ok(Json(wallet.get_addresses(
+        addresses_req.keychain,
+        addresses_req.start,
+        addresses_req.count,
+        addresses_req.sync,
+    )?))
+}
+
+/// Returns the keychain, derivation index, script and cosigner public keys of the address if it belongs to the
+/// wallet described by the descriptors, otherwise the found field is set to false
+///
+/// # Arguments
+///
+/// * `verify_address_req` - A VerifyAddressRequest object with the descriptors, the address and the number of
+/// indexes of each keychain to search
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or address, or an address of another network
+#[post("/verify_address?<stop_gap>", data = "<verify_address_req>")]
+fn verify_address(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    verify_address_req: Json<VerifyAddressRequest>,
+) -> Result<Json<AddressVerification>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = with_labels(
+        config,
+        Wallet::from_descriptors(&blockchain, &verify_address_req.descriptors)?,
+    )?;
+    Ok(Json(wallet.verify_address(
+        &verify_address_req.address,
+        verify_address_req.search_window,
+    )?))
+}
+
+/// Imports BIP-329 labels for the wallet described by the descriptors, existing labels with the same type and
+/// reference are replaced
+///
+/// # Arguments
+///
+/// * `import_labels_req` - An ImportLabelsRequest object with the descriptors and the labels as BIP-329 JSON lines
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or label
+#[post("/import_labels", data = "<import_labels_req>")]
+fn import_labels(
+    config: &State<Config>,
+    import_labels_req: Json<ImportLabelsRequest>,
+) -> Result<Json<ImportedLabels>, Error> {
+    import_labels_req
+        .descriptors
+        .verify_network(config.network)?;
+    let descriptor_checksum = import_labels_req.descriptors.checksum()?;
+    let storage = Storage::new(&config.storage_path);
+    let imported =
+        LabelStore::new(&storage).import(&descriptor_checksum, &import_labels_req.content)?;
+    Ok(Json(ImportedLabels {
+        descriptor_checksum,
+        imported,
+    }))
+}
+
+/// Returns the labels of the wallet described by the descriptors as BIP-329 JSON lines
 ///
 /// # Arguments
 ///
@@ -75,17 +244,89 @@ fn gen_new_address(
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid descriptor
-#[post("/list_trxs", data = "<descriptors>")]
+#[post("/export_labels", data = "<descriptors>")]
+fn export_labels(config: &State<Config>, descriptors: Json<Descriptors>) -> Result<String, Error> {
+    descriptors.verify_network(config.network)?;
+    let storage = Storage::new(&config.storage_path);
+    LabelStore::new(&storage).export(&descriptors.checksum()?)
+}
+
+/// Returns a page of the trxs for the provided output descriptors, ordered by confirmation height
+///
+/// # Arguments
+///
+/// * `list_trxs_req` - A ListTrxsRequest object with the descriptor field set, the change descriptor is optional,
+/// and optionally the limit, cursor, order, height and time ranges, direction and confirmed filters
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or an unknown cursor trx
+#[post("/list_trxs?<stop_gap>", data = "<list_trxs_req>")]
 fn list_trxs(
     config: &State<Config>,
-    descriptors: Json<Descriptors>,
+    stop_gap: Option<&str>,
+    list_trxs_req: Json<ListTrxsRequest>,
 ) -> Result<Json<Vec<TrxDetails>>, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
-    let wallet = Wallet::from_descriptors(&blockchain, &descriptors)?;
-    let trxs = wallet.list_trxs()?;
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = with_labels(
+        config,
+        Wallet::from_descriptors(&blockchain, &list_trxs_req.descriptors)?,
+    )?;
+    let trxs = wallet.list_trxs(&list_trxs_req.filter)?;
     Ok(Json(trxs))
 }
 
+/// Streams the trx history of the wallet described by the descriptors as CSV, with the date, txid,
+/// direction, amount, fee, running balance, confirmation height and label of each trx
+///
+/// # Arguments
+///
+/// * `export_trxs_req` - An ExportTrxsRequest object with the descriptor field set, the change descriptor is optional,
+/// and optionally the start and end time of the range
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor
+#[post("/export_trxs?<stop_gap>", data = "<export_trxs_req>")]
+fn export_trxs(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    export_trxs_req: Json<ExportTrxsRequest>,
+) -> Result<(ContentType, TextStream<impl Stream<Item = String>>), Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = with_labels(
+        config,
+        Wallet::from_descriptors(&blockchain, &export_trxs_req.descriptors)?,
+    )?;
+    let lines = wallet.export_trxs(export_trxs_req.start_time, export_trxs_req.end_time)?;
+    Ok((ContentType::CSV, TextStream(stream::iter(lines))))
+}
+
+/// Returns the details of a trx of the wallet described by the descriptors, with its raw hex, block
+/// hash and the cosigners that signed each of its inputs
+///
+/// # Arguments
+///
+/// * `get_trx_req` - A GetTrxRequest object with the descriptors and txid fields set
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or a trx not found in the wallet
+#[post("/get_trx?<stop_gap>", data = "<get_trx_req>")]
+fn get_trx(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    get_trx_req: Json<GetTrxRequest>,
+) -> Result<Json<TrxWithSigners>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = with_labels(
+        config,
+        Wallet::from_descriptors(&blockchain, &get_trx_req.descriptors)?,
+    )?;
+    let trx = wallet.get_trx(&get_trx_req.txid)?;
+    Ok(Json(trx))
+}
+
 /// Returns a list of xpubs who signed the psbt
 ///
 /// # Arguments
@@ -95,12 +336,13 @@ fn list_trxs(
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid descriptors or psbt
-#[post("/list_signers", data = "<verify_psbt_payload>")]
+#[post("/list_signers?<stop_gap>", data = "<verify_psbt_payload>")]
 fn list_signers(
     config: &State<Config>,
+    stop_gap: Option<&str>,
     verify_psbt_payload: Json<VerifyPSBTPayload>,
 ) -> Result<Json<Vec<Cosigner>>, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &verify_psbt_payload.descriptors)?;
     let signers = wallet.get_signers(&verify_psbt_payload.psbt, config.pub_key_search_radius)?;
     Ok(Json(signers))
@@ -120,9 +362,8 @@ fn gen_multisig(
     config: &State<Config>,
     descriptors: Json<Descriptors>,
 ) -> Result<Json<Multisig>, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
-    let wallet = Wallet::from_descriptors(&blockchain, &descriptors)?;
-    Ok(Json(wallet.get_multisig()?))
+    descriptors.verify_network(config.network)?;
+    Ok(Json(Multisig::from_descriptors(&descriptors)?))
 }
 
 /// Returns a Descriptor object with the descriptor and change_descriptor fields set for the provided multisig
@@ -140,10 +381,173 @@ fn gen_output_descriptor(
     config: &State<Config>,
     multisig: Json<Multisig>,
 ) -> Result<Json<Descriptors>, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
-    let wallet = Wallet::from_multisig(&blockchain, &multisig)?;
-    let descriptors = wallet.get_descriptors()?;
-    Ok(Json(descriptors))
+    multisig.verify_network(config.network)?;
+    Ok(Json(multisig.descriptors()?))
+}
+
+/// Returns the SLIP-132 version, network and script type of the provided xpub, along with the xpub converted to
+/// the requested version
+///
+/// # Arguments
+///
+/// * `convert_xpub_req` - A ConvertXpubRequest object with the xpub and optionally the version to convert it to,
+/// any of xpub, ypub, zpub, Ypub, Zpub, tpub, upub, vpub, Upub or Vpub
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid xpub checksum, or an unknown xpub or target version
+#[post("/convert_xpub", data = "<convert_xpub_req>")]
+fn convert_xpub(convert_xpub_req: Json<ConvertXpubRequest>) -> Result<Json<ConvertedXpub>, Error> {
+    Ok(Json(ConvertedXpub::new(
+        &convert_xpub_req.xpub,
+        convert_xpub_req.version.as_deref(),
+    )?))
+}
+
+/// Returns a challenge for the proof of ownership of a cosigner key, it expires after 10 minutes
+/// and can only be used once for each key
+#[get("/cosigner_challenge")]
+fn cosigner_challenge() -> Json<CosignerChallenge> {
+    Json(CosignerChallenge::new())
+}
+
+/// Returns the descriptors and the multisig for the provided wallet configuration file
+///
+/// # Arguments
+///
+/// * `import_wallet_req` - An ImportWalletRequest object with the content of a Coldcard, Specter, Sparrow
+/// or Caravan wallet configuration file, the format is detected from the content when not provided
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid or unsupported wallet configuration
+#[post("/import_wallet", data = "<import_wallet_req>")]
+fn import_wallet(
+    config: &State<Config>,
+    import_wallet_req: Json<ImportWalletRequest>,
+) -> Result<Json<ImportedWallet>, Error> {
+    let wallet_config =
+        wallet_config::import(&import_wallet_req.content, import_wallet_req.format)?;
+    wallet_config.multisig.verify_network(config.network)?;
+    Ok(Json(ImportedWallet {
+        name: wallet_config.name,
+        descriptors: wallet_config.multisig.descriptors()?,
+        multisig: wallet_config.multisig,
+    }))
+}
+
+/// Returns the wallet configuration file used to register the multisig on hardware and desktop wallets
+///
+/// # Arguments
+///
+/// * `export_wallet_req` - An ExportWalletRequest object with either the descriptors or the multisig set, and
+/// the format of the file: coldcard, specter, sparrow, caravan or bitcoin-core
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or multisig, or if a cosigner is missing its xfp or
+/// derivation path
+#[post("/export_wallet", data = "<export_wallet_req>")]
+fn export_wallet(
+    config: &State<Config>,
+    export_wallet_req: Json<ExportWalletRequest>,
+) -> Result<String, Error> {
+    let descriptors = match (&export_wallet_req.multisig, &export_wallet_req.descriptors) {
+        (Some(multisig), _) => {
+            multisig.verify_network(config.network)?;
+            multisig.descriptors()?
+        }
+        (None, Some(descriptors)) => {
+            descriptors.verify_network(config.network)?;
+            Multisig::from_descriptors(descriptors)?.descriptors()?
+        }
+        (None, None) => {
+            return Err(Error::new(
+                "either descriptors or multisig must be provided",
+            ))
+        }
+    };
+    let multisig = Multisig::from_descriptors(&descriptors)?;
+    wallet_config::export(
+        export_wallet_req.format,
+        export_wallet_req.name.as_deref().unwrap_or("Vault"),
+        &multisig,
+        &descriptors,
+    )
+}
+
+/// Creates a multisig setup session exchanging plain text key records, returns the session with the token
+/// for each signer. This is the only response that includes the tokens, each token must be handed privately
+/// to its signer
+///
+/// # Arguments
+///
+/// * `bsms_session_req` - A BsmsSessionRequest object with the threshold and the number of signers
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid threshold or number of signers
+#[post("/bsms_create_session", data = "<bsms_session_req>")]
+fn bsms_create_session(
+    config: &State<Config>,
+    bsms_session_req: Json<BsmsSessionRequest>,
+) -> Result<Json<CreatedBsmsSession>, Error> {
+    let storage = Storage::new(&config.storage_path);
+    let coordinator = bsms::Coordinator::new(&storage);
+    Ok(Json(coordinator.create_session(
+        bsms_session_req.threshold,
+        bsms_session_req.signers,
+    )?))
+}
+
+/// Adds the signed key record of a signer to a multisig setup session, returns the progress of the session
+///
+/// # Arguments
+///
+/// * `bsms_key_record_req` - A BsmsKeyRecordRequest object with the session id and the key record
+///
+/// # Errors
+///
+/// Returns 404 error in case of an unknown session or token, or if the key record signature is invalid
+#[post("/bsms_add_key_record", data = "<bsms_key_record_req>")]
+fn bsms_add_key_record(
+    config: &State<Config>,
+    bsms_key_record_req: Json<BsmsKeyRecordRequest>,
+) -> Result<Json<BsmsSessionStatus>, Error> {
+    let storage = Storage::new(&config.storage_path);
+    let coordinator = bsms::Coordinator::new(&storage);
+    Ok(Json(coordinator.add_key_record(
+        &bsms_key_record_req.session_id,
+        &bsms_key_record_req.key_record,
+    )?))
+}
+
+/// Returns the plain text descriptor record for a multisig setup session, along with the descriptors and the
+/// multisig
+///
+/// # Arguments
+///
+/// * `bsms_descriptor_record_req` - A BsmsDescriptorRecordRequest object with the session id
+///
+/// # Errors
+///
+/// Returns 404 error in case of an unknown session or if there are key records missing
+#[post("/bsms_descriptor_record", data = "<bsms_descriptor_record_req>")]
+fn bsms_descriptor_record(
+    config: &State<Config>,
+    bsms_descriptor_record_req: Json<BsmsDescriptorRecordRequest>,
+) -> Result<Json<BsmsDescriptorRecord>, Error> {
+    let storage = Storage::new(&config.storage_path);
+    let coordinator = bsms::Coordinator::new(&storage);
+    let multisig = coordinator
+        .get_session(&bsms_descriptor_record_req.session_id)?
+        .multisig()?;
+    multisig.verify_network(config.network)?;
+    Ok(Json(BsmsDescriptorRecord {
+        descriptor_record: bsms::descriptor_record(&multisig, config.network)?,
+        descriptors: multisig.descriptors()?,
+        multisig,
+    }))
 }
 
 /// Returns a psbt as a base64 encoded string for the provided Trx object
@@ -155,11 +559,15 @@ fn gen_output_descriptor(
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid trx
-#[post("/gen_psbt", data = "<trx>")]
-fn gen_psbt(config: &State<Config>, trx: Json<Trx>) -> Result<String, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+#[post("/gen_psbt?<stop_gap>", data = "<trx>")]
+fn gen_psbt(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    trx: Json<Trx>,
+) -> Result<String, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &trx.descriptors)?;
-    Ok(wallet.build_tx_encoded(&trx)?)
+    wallet.build_tx_encoded(&trx)
 }
 
 /// Returns proof of reserves as a base64 encoded psbt string for the wallet described by the descriptors
@@ -171,14 +579,15 @@ fn gen_psbt(config: &State<Config>, trx: Json<Trx>) -> Result<String, Error> {
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid descriptors
-#[post("/create_proof", data = "<proof_of_reserves_req>")]
+#[post("/create_proof?<stop_gap>", data = "<proof_of_reserves_req>")]
 fn create_proof_of_reserves(
     config: &State<Config>,
+    stop_gap: Option<&str>,
     proof_of_reserves_req: Json<ProofOfReservesRequest>,
 ) -> Result<String, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &proof_of_reserves_req.descriptors)?;
-    Ok(wallet.create_proof_of_reserves_encoded(&proof_of_reserves_req.message)?)
+    wallet.create_proof_of_reserves_encoded(&proof_of_reserves_req.message)
 }
 
 /// Finalizes and broadcasts a trx based on the provided signed psbts, returns the trx ID in
@@ -191,11 +600,15 @@ fn create_proof_of_reserves(
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid signed trx object
-#[post("/finalize_trx", data = "<signed_trx>")]
-fn finalize_trx(config: &State<Config>, signed_trx: Json<SignedTrx>) -> Result<String, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+#[post("/finalize_trx?<stop_gap>", data = "<signed_trx>")]
+fn finalize_trx(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    signed_trx: Json<SignedTrx>,
+) -> Result<String, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &signed_trx.descriptors)?;
-    Ok(wallet.finalize_trx(signed_trx.psbts.as_slice(), signed_trx.broadcast)?)
+    wallet.finalize_trx(signed_trx.psbts.as_slice(), signed_trx.broadcast)
 }
 
 /// Finalizes proof based on the provided signed psbts, returns the combined psbt in
@@ -208,14 +621,37 @@ fn finalize_trx(config: &State<Config>, signed_trx: Json<SignedTrx>) -> Result<S
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid signed proof of reserves object
-#[post("/finalize_proof", data = "<signed_proof>")]
+#[post("/finalize_proof?<stop_gap>", data = "<signed_proof>")]
 fn finalize_proof_of_reserves(
     config: &State<Config>,
+    stop_gap: Option<&str>,
     signed_proof: Json<SignedProofOfReserves>,
 ) -> Result<String, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &signed_proof.descriptors)?;
-    Ok(wallet.finalize_proof_of_reserves(signed_proof.psbts.as_slice())?)
+    wallet.finalize_proof_of_reserves(signed_proof.psbts.as_slice())
+}
+
+/// Returns the cosigners that signed each input of the proof of reserves and the ones still
+/// pending, along with the threshold of the wallet
+///
+/// # Arguments
+///
+/// * `signed_proof` - A SignedProofOfReserves object with the descriptors and signed psbts
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or psbt
+#[post("/proof_signers?<stop_gap>", data = "<signed_proof>")]
+fn list_proof_signers(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    signed_proof: Json<SignedProofOfReserves>,
+) -> Result<Json<ProofSignersStatus>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = Wallet::from_descriptors(&blockchain, &signed_proof.descriptors)?;
+    let status = wallet.get_proof_signers(&signed_proof.psbts)?;
+    Ok(Json(status))
 }
 
 /// Verify proof of reserves
@@ -223,24 +659,171 @@ fn finalize_proof_of_reserves(
 ///
 /// # Arguments
 ///
-/// * `proof_of_reserves` - A ProofOfReverse object with the output descriptor, finalized psbt and message
+/// * `proof_of_reserves` - A ProofOfReverse object with the output descriptor, finalized psbt and message,
+/// and optionally the max block height and min confirmations of the proof outputs
 ///
 /// # Errors
 ///
 /// Returns 404 error in case of an invalid signed proof of reserves object
-#[post("/verify_proof", data = "<proof_of_reserves>")]
+#[post("/verify_proof?<stop_gap>", data = "<proof_of_reserves>")]
 fn verify_proof_of_reserves(
     config: &State<Config>,
+    stop_gap: Option<&str>,
     proof_of_reserves: Json<ProofOfReserves>,
-) -> Result<String, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+) -> Result<Json<ProofOfReservesVerification>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &proof_of_reserves.descriptors)?;
-    Ok(wallet
-        .verify_proof_of_reserves(&proof_of_reserves.message, &proof_of_reserves.psbt)?
-        .to_string())
+    let verification = wallet.verify_proof_of_reserves(
+        &proof_of_reserves.message,
+        &proof_of_reserves.psbt,
+        proof_of_reserves.max_block_height,
+        proof_of_reserves.min_confirmations,
+    )?;
+    Ok(Json(verification))
+}
+
+/// Returns a proof of reserves spanning all the wallets described by the descriptors, as a base64
+/// encoded psbt with a single challenge input followed by the outputs of each wallet
+///
+/// # Arguments
+///
+/// * `proof_req` - An AggregatedProofOfReservesRequest object with the descriptors of the wallets and the message
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid or repeated descriptor
+#[post("/create_aggregated_proof?<stop_gap>", data = "<proof_req>")]
+fn create_aggregated_proof(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    proof_req: Json<AggregatedProofOfReservesRequest>,
+) -> Result<String, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallets = wallets_from_descriptors(&blockchain, &proof_req.descriptors)?;
+    reserves::create_proof(&wallets, &proof_req.message)
+}
+
+/// Finalizes the aggregated proof based on the provided signed psbts, returns the combined psbt in
+/// case of success
+///
+/// # Arguments
+///
+/// * `signed_proof` - A SignedAggregatedProofOfReserves object with the descriptors of the wallets and signed psbts
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or if the psbts do not finalize the proof
+#[post("/finalize_aggregated_proof?<stop_gap>", data = "<signed_proof>")]
+fn finalize_aggregated_proof(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    signed_proof: Json<SignedAggregatedProofOfReserves>,
+) -> Result<String, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallets = wallets_from_descriptors(&blockchain, &signed_proof.descriptors)?;
+    reserves::finalize_proof(&wallets, &signed_proof.psbts)
+}
+
+/// Verifies the aggregated proof of reserves, returns the total amount and the amount of each
+/// wallet
+///
+/// # Arguments
+///
+/// * `proof` - An AggregatedProofOfReserves object with the descriptors of the wallets, finalized psbt and message,
+/// and optionally the max block height and min confirmations of the proof outputs
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or proof
+#[post("/verify_aggregated_proof?<stop_gap>", data = "<proof>")]
+fn verify_aggregated_proof(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    proof: Json<AggregatedProofOfReserves>,
+) -> Result<Json<AggregatedProofOfReservesVerification>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallets = wallets_from_descriptors(&blockchain, &proof.descriptors)?;
+    let verification = reserves::verify_proof(
+        &wallets,
+        &proof.message,
+        &proof.psbt,
+        proof.max_block_height,
+        proof.min_confirmations,
+    )?;
+    Ok(Json(verification))
+}
+
+/// Verifies the proof of reserves without syncing any wallet, against the supplied utxos or the
+/// utxo data of the psbt, the report states which of them the proof was verified against
+///
+/// # Arguments
+///
+/// * `proof` - An OfflineProofOfReserves object with the finalized psbt and message, and optionally
+/// the utxos and the block height of their snapshot
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid proof or if the utxo data of an input is missing
+#[post("/verify_proof_offline", data = "<proof>")]
+fn verify_proof_offline(
+    config: &State<Config>,
+    proof: Json<OfflineProofOfReserves>,
+) -> Result<Json<ProofOfReservesVerification>, Error> {
+    let verification = reserves::verify_proof_offline(&proof, config.network)?;
+    Ok(Json(verification))
 }
 
-/// Returns balance in sats for the provided output descriptor
+/// Returns a BIP-322 proof of control of an address of the wallet for the message, as a base64
+/// encoded psbt of the "to_sign" trx for the cosigners to sign
+///
+/// # Arguments
+///
+/// * `proof_req` - A MessageProofRequest object with the descriptors, the address and the message
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or if the address does not belong to the wallet
+#[post("/create_message_proof?<stop_gap>", data = "<proof_req>")]
+fn create_message_proof(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    proof_req: Json<MessageProofRequest>,
+) -> Result<String, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = Wallet::from_descriptors(&blockchain, &proof_req.descriptors)?;
+    bip322::create_proof(&wallet, &proof_req.address, &proof_req.message)
+}
+
+/// Finalizes the signed BIP-322 proof and verifies the signature of the message, returns the
+/// signers and the signature in case of success
+///
+/// # Arguments
+///
+/// * `signed_proof` - A SignedMessageProof object with the descriptors, the address, the message and signed psbts
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor, if the psbts do not finalize the proof or
+/// if the signature is invalid
+#[post("/verify_message_proof?<stop_gap>", data = "<signed_proof>")]
+fn verify_message_proof(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    signed_proof: Json<SignedMessageProof>,
+) -> Result<Json<MessageProofVerification>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = Wallet::from_descriptors(&blockchain, &signed_proof.descriptors)?;
+    let verification = bip322::verify_proof(
+        &wallet,
+        &signed_proof.address,
+        &signed_proof.message,
+        &signed_proof.psbts,
+    )?;
+    Ok(Json(verification))
+}
+
+/// Returns the sync status of the wallet: last sync, chain tip of the backend, derivation indexes
+/// and gap limit status of each keychain and number of utxos
 ///
 /// # Arguments
 ///
@@ -248,12 +831,66 @@ fn verify_proof_of_reserves(
 ///
 /// # Errors
 ///
-/// Returns 404 error in case of an invalid descriptor
-#[post("/get_balance", data = "<descriptors>")]
-fn get_balance(config: &State<Config>, descriptors: Json<Descriptors>) -> Result<String, Error> {
-    let blockchain = Blockchain::new(&config.network_url, config.network).unwrap();
+/// Returns 404 error in case of an invalid descriptor or if the backend is not reachable
+#[post("/wallet_status?<stop_gap>", data = "<descriptors>")]
+fn wallet_status(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    descriptors: Json<Descriptors>,
+) -> Result<Json<WalletStatus>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
     let wallet = Wallet::from_descriptors(&blockchain, &descriptors)?;
-    let balance = wallet.get_balance()?;
+    let status = wallet.get_status()?;
+    Ok(Json(status))
+}
+
+/// Rescans the addresses of both keychains from index 0 up to the end index, beyond the stop gap of
+/// a regular sync, returns the used addresses within the range and the stop gap needed to find them
+/// all
+///
+/// # Arguments
+///
+/// * `rescan_req` - A RescanRequest object with the descriptor field set, the change descriptor is optional,
+/// and the start and end index of the range, the start index only filters the returned addresses
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or range
+#[post("/rescan?<stop_gap>", data = "<rescan_req>")]
+fn rescan(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    rescan_req: Json<RescanRequest>,
+) -> Result<Json<RescanResult>, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = Wallet::from_descriptors(&blockchain, &rescan_req.descriptors)?;
+    let result = wallet.rescan(rescan_req.start_index, rescan_req.end_index)?;
+    Ok(Json(result))
+}
+
+/// Returns balance in sats for the provided output descriptor, or the confirmed balance at a block
+/// height or time when one is set
+///
+/// # Arguments
+///
+/// * `balance_req` - A BalanceRequest object with the descriptor field set, the change descriptor is optional,
+/// and optionally the at_height or the at_time of the balance
+///
+/// # Errors
+///
+/// Returns 404 error in case of an invalid descriptor or if both the height and the time are set
+#[post("/get_balance?<stop_gap>", data = "<balance_req>")]
+fn get_balance(
+    config: &State<Config>,
+    stop_gap: Option<&str>,
+    balance_req: Json<BalanceRequest>,
+) -> Result<String, Error> {
+    let blockchain = new_blockchain(config, stop_gap)?;
+    let wallet = Wallet::from_descriptors(&blockchain, &balance_req.descriptors)?;
+    let balance = match BalanceSnapshot::new(balance_req.at_height, balance_req.at_time)? {
+        Some(snapshot) => wallet.get_balance_at(snapshot)?,
+        None => wallet.get_balance()?,
+    };
     Ok(balance.to_string())
 }
 
@@ -270,16 +907,38 @@ fn rocket() -> _ {
             routes![
                 index,
                 gen_output_descriptor,
+                convert_xpub,
+                cosigner_challenge,
+                import_wallet,
+                export_wallet,
+                bsms_create_session,
+                bsms_add_key_record,
+                bsms_descriptor_record,
                 gen_new_address,
+                get_addresses,
+                verify_address,
+                import_labels,
+                export_labels,
                 gen_psbt,
                 finalize_trx,
                 gen_multisig,
                 get_balance,
+                wallet_status,
+                rescan,
                 list_trxs,
+                get_trx,
+                export_trxs,
                 list_signers,
                 create_proof_of_reserves,
                 finalize_proof_of_reserves,
+                list_proof_signers,
                 verify_proof_of_reserves,
+                create_aggregated_proof,
+                finalize_aggregated_proof,
+                verify_aggregated_proof,
+                verify_proof_offline,
+                create_message_proof,
+                verify_message_proof,
                 options
             ],
         )